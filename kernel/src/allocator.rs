@@ -1,23 +1,71 @@
 use alloc::alloc::{GlobalAlloc, Layout};
-use core::ptr::null_mut;
+use alloc::vec::Vec;
+use core::ptr::{null_mut, NonNull};
+use core::sync::atomic::{AtomicUsize, Ordering};
 use x86_64::{
     structures::paging::{
-        mapper::MapToError, FrameAllocator, Mapper, Page, PageTableFlags, Size4KiB,
+        mapper::MapToError, FrameAllocator, FrameDeallocator, Mapper, Page, PageTableFlags, Size4KiB,
     },
     VirtAddr,
 };
-use fixed_size_block::FixedSizeBlockAllocator;
+use crate::memory;
 
 pub mod bump;
 pub mod linked_list;
 pub mod fixed_size_block;
+pub mod buddy;
+pub mod large;
+pub mod growable;
+pub mod slab;
+pub mod debug;
 
 pub const HEAP_START: usize = 0x_4444_4444_0000;
-pub const HEAP_SIZE: usize = 100 * 1024; // 100 KiB
+pub const HEAP_SIZE: usize = 100 * 1024; // 100 KiB to start with, grows on demand
+/// Default limit for the heap size, see `set_heap_limit`.
+pub const HEAP_MAX_SIZE: usize = 16 * 1024 * 1024; // 16 MiB
+const HEAP_GROWTH: usize = 64 * 1024; // the heap grows by at least this much at a time
+const PAGE_SIZE: usize = 4096;
 
+static HEAP_LIMIT: AtomicUsize = AtomicUsize::new(HEAP_MAX_SIZE);
+
+// The global allocator is picked at build time with exactly one of the `alloc-*` features,
+// `alloc-fixed-block` is the default: `cargo test --no-default-features --features alloc-buddy`.
+#[cfg(feature = "alloc-bump")]
+type GlobalHeap = bump::BumpAllocator;
+#[cfg(feature = "alloc-linked-list")]
+type GlobalHeap = linked_list::LinkedListAllocator;
+#[cfg(feature = "alloc-fixed-block")]
+type GlobalHeap = fixed_size_block::FixedSizeBlockAllocator;
+#[cfg(feature = "alloc-buddy")]
+type GlobalHeap = buddy::BuddyAllocator;
+
+#[cfg(not(any(
+    feature = "alloc-bump",
+    feature = "alloc-linked-list",
+    feature = "alloc-fixed-block",
+    feature = "alloc-buddy"
+)))]
+compile_error!("enable one of the features alloc-bump, alloc-linked-list, alloc-fixed-block or alloc-buddy");
+
+#[cfg(any(
+    all(feature = "alloc-bump", any(feature = "alloc-linked-list", feature = "alloc-fixed-block", feature = "alloc-buddy")),
+    all(feature = "alloc-linked-list", any(feature = "alloc-fixed-block", feature = "alloc-buddy")),
+    all(feature = "alloc-fixed-block", feature = "alloc-buddy")
+))]
+compile_error!("only one alloc-* feature can be enabled, add --no-default-features to pick another one than alloc-fixed-block");
+
+#[cfg(not(feature = "debug-alloc"))]
+#[global_allocator]
+static ALLOCATOR: growable::GrowableHeap<GlobalHeap> = growable::GrowableHeap::new(
+    GlobalHeap::new());
+
+// `debug-alloc` puts the debug allocator in front of the heap, ALLOCATOR stays the heap itself
+#[cfg(feature = "debug-alloc")]
 #[global_allocator]
-static ALLOCATOR: Locked<FixedSizeBlockAllocator> = Locked::new(
-    FixedSizeBlockAllocator::new());
+static DEBUG_ALLOCATOR: debug::DebugAllocator<growable::GrowableHeap<GlobalHeap>> =
+    debug::DebugAllocator::new(growable::GrowableHeap::new(GlobalHeap::new()));
+#[cfg(feature = "debug-alloc")]
+static ALLOCATOR: &growable::GrowableHeap<GlobalHeap> = DEBUG_ALLOCATOR.inner();
 
 pub fn init_heap( // fn to initialize the heap
     mapper: &mut impl Mapper<Size4KiB>, // Mapper is used to map pages to physical frames
@@ -40,12 +88,297 @@ pub fn init_heap( // fn to initialize the heap
     }
 
     unsafe {
-        ALLOCATOR.lock().init(HEAP_START, HEAP_SIZE); // init the LockedHeap allocator
+        HeapAllocator::init(&mut *ALLOCATOR.lock(), HEAP_START, HEAP_SIZE); // init the LockedHeap allocator
     }
+    crate::log_info!("heap: {} KiB at {:#x}, {} allocator", HEAP_SIZE / 1024, HEAP_START, GlobalHeap::NAME);
 
     Ok(()) // if success, return Ok
 }
 
+/// Returns the used and free bytes of the kernel heap.
+pub fn heap_usage() -> (usize, usize) {
+    let allocator = ALLOCATOR.lock();
+    (HeapAllocator::used(&*allocator), HeapAllocator::free(&*allocator))
+}
+
+/// Returns how many bytes are mapped for the heap right now.
+pub fn heap_size() -> usize {
+    HeapAllocator::size(&*ALLOCATOR.lock())
+}
+
+/// The size the heap may grow to.
+pub fn heap_limit() -> usize {
+    HEAP_LIMIT.load(Ordering::Relaxed)
+}
+
+/// Sets the size the heap may grow to. A heap that is already bigger stays as it is.
+pub fn set_heap_limit(size: usize) {
+    HEAP_LIMIT.store(size & !(PAGE_SIZE - 1), Ordering::Relaxed);
+}
+
+/// Maps more memory at `heap_top` after an allocation of `layout` didn't fit into a heap
+/// of `heap_size` bytes. Returns how many bytes were mapped, or None if the heap is at its
+/// limit or the page tables are busy. Runs with the heap locked, so it must not allocate.
+fn grow_heap(heap_top: usize, heap_size: usize, layout: &Layout) -> Option<usize> {
+    let room = heap_limit().saturating_sub(heap_size);
+    // worst case the allocation can't use any of the free memory below the top, and needs a
+    // block aligned to its (power of two) size like in the buddy allocator: twice that always has one
+    let needed = align_up(2 * layout.size().max(layout.align()).next_power_of_two(), PAGE_SIZE);
+    if needed > room {
+        return None;
+    }
+    let size = needed.max(HEAP_GROWTH).min(room);
+    map_pages(heap_top, size).ok()?;
+    Some(size)
+}
+
+/// Unmaps the free memory at the top of `heap` once there is at least HEAP_GROWTH of it,
+/// but never below the HEAP_SIZE the heap started with. Runs with the heap locked, like `grow_heap`.
+fn shrink_heap(heap: &mut impl HeapAllocator) {
+    let free = heap.free_at_top().min(heap.size().saturating_sub(HEAP_SIZE));
+    let mut size = free & !(PAGE_SIZE - 1);
+    if size < HEAP_GROWTH {
+        return;
+    }
+    unsafe {
+        // a page less leaves more below, if what would be left is too small to keep
+        if !heap.shrink(size) {
+            size -= PAGE_SIZE;
+            if !heap.shrink(size) {
+                return;
+            }
+        }
+        if !unmap_pages(heap.top(), size) {
+            heap.extend(size); // the page tables are busy, the next free tries again
+        }
+    }
+}
+
+/// Maps `size` bytes at `start` to fresh frames, with the mapper and frame allocator in `memory`.
+/// Fails instead of waiting if someone else is using them. On failure nothing stays mapped.
+fn map_pages(start: usize, size: usize) -> Result<(), MapToError<Size4KiB>> {
+    let mut mapper = memory::MAPPER.try_lock().ok_or(MapToError::FrameAllocationFailed)?;
+    let mut frames = memory::FRAME_ALLOCATOR.try_lock().ok_or(MapToError::FrameAllocationFailed)?;
+    let (mapper, frames) = match (mapper.as_mut(), frames.as_mut()) {
+        (Some(mapper), Some(frames)) => (mapper, frames),
+        _ => return Err(MapToError::FrameAllocationFailed), // not handed over yet
+    };
+
+    let flags = PageTableFlags::PRESENT | PageTableFlags::WRITABLE;
+    for offset in (0..size).step_by(PAGE_SIZE) {
+        let page = Page::containing_address(VirtAddr::new((start + offset) as u64));
+        let result = match frames.allocate_frame() {
+            Some(frame) => unsafe { mapper.map_to(page, frame, flags, frames) },
+            None => Err(MapToError::FrameAllocationFailed),
+        };
+        match result {
+            Ok(flush) => flush.flush(),
+            Err(error) => {
+                unmap_with(mapper, frames, start, offset);
+                return Err(error);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Unmaps `size` bytes at `start` that `map_pages` mapped and gives the frames back.
+/// Returns false (and leaves them mapped) if someone else is using the page tables:
+/// there is one core, so waiting for them would never end.
+fn unmap_pages(start: usize, size: usize) -> bool {
+    let (mut mapper, mut frames) = match (memory::MAPPER.try_lock(), memory::FRAME_ALLOCATOR.try_lock()) {
+        (Some(mapper), Some(frames)) => (mapper, frames),
+        _ => return false,
+    };
+    match (mapper.as_mut(), frames.as_mut()) {
+        (Some(mapper), Some(frames)) => {
+            unmap_with(mapper, frames, start, size);
+            true
+        }
+        _ => false,
+    }
+}
+
+fn unmap_with(
+    mapper: &mut impl Mapper<Size4KiB>,
+    frames: &mut impl FrameDeallocator<Size4KiB>,
+    start: usize,
+    size: usize,
+) {
+    for offset in (0..size).step_by(PAGE_SIZE) {
+        let page = Page::<Size4KiB>::containing_address(VirtAddr::new((start + offset) as u64));
+        if let Ok((frame, flush)) = mapper.unmap(page) {
+            flush.flush();
+            unsafe { frames.deallocate_frame(frame) };
+        }
+    }
+}
+
+/// Returns the usage counters of the kernel heap.
+pub fn heap_stats() -> HeapStats {
+    HeapAllocator::stats(&*ALLOCATOR.lock())
+}
+
+/// Returns how many blocks of each size class are handed out and parked in the free lists,
+/// None if the global allocator has no block lists.
+pub fn size_classes() -> Option<[SizeClass; fixed_size_block::SIZE_CLASSES]> {
+    HeapAllocator::size_classes(&*ALLOCATOR.lock())
+}
+
+/// Name of the global allocator, as in the feature that picked it.
+pub fn allocator_name() -> &'static str {
+    GlobalHeap::NAME
+}
+
+/// Returns the allocations that weren't freed yet, oldest first, or None if the kernel
+/// isn't built with the `debug-alloc` feature.
+pub fn live_allocations() -> Option<Vec<debug::LiveAllocation>> {
+    #[cfg(feature = "debug-alloc")]
+    return Some(DEBUG_ALLOCATOR.live_allocations());
+    #[cfg(not(feature = "debug-alloc"))]
+    None
+}
+
+/// Usage counters kept by the heap allocators. Sizes are the ones the callers asked for,
+/// so the difference to the heap's used bytes is lost to rounding and parked blocks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HeapStats {
+    pub bytes_allocated: usize,      // bytes in live allocations
+    pub live_allocations: usize,
+    pub total_allocations: usize,    // allocations since boot
+    pub peak_bytes: usize,           // most bytes_allocated ever was
+    pub fallback_allocations: usize, // allocations too large for the block lists, large ones aside
+}
+
+impl HeapStats {
+    pub const fn new() -> Self {
+        HeapStats {
+            bytes_allocated: 0,
+            live_allocations: 0,
+            total_allocations: 0,
+            peak_bytes: 0,
+            fallback_allocations: 0,
+        }
+    }
+
+    fn record_alloc(&mut self, size: usize) {
+        self.bytes_allocated += size;
+        self.live_allocations += 1;
+        self.total_allocations += 1;
+        self.peak_bytes = self.peak_bytes.max(self.bytes_allocated);
+    }
+
+    fn record_dealloc(&mut self, size: usize) {
+        self.bytes_allocated -= size;
+        self.live_allocations -= 1;
+    }
+
+    // a block resized where it is: still the same allocation, only its size changed
+    fn record_resize(&mut self, old_size: usize, new_size: usize) {
+        self.bytes_allocated = self.bytes_allocated - old_size + new_size;
+        self.peak_bytes = self.peak_bytes.max(self.bytes_allocated);
+    }
+}
+
+pub use fixed_size_block::SizeClass;
+
+/// What the kernel asks of the global allocator besides `GlobalAlloc`. Implemented by every
+/// allocator an `alloc-*` feature can pick. The allocators only manage the region they are
+/// given, `GrowableHeap` maps and unmaps memory at its top with `extend` and `shrink`.
+pub trait HeapAllocator {
+    const NAME: &'static str;
+
+    /// Unsafe because the caller must guarantee that the region is mapped and unused. Call only once.
+    unsafe fn init(&mut self, heap_start: usize, heap_size: usize);
+    /// Adds `by` bytes right after `top`. Unsafe for the same reasons as `init`.
+    unsafe fn extend(&mut self, by: usize);
+    /// Bytes handed out, including what is lost to rounding up.
+    fn used(&self) -> usize;
+    /// Bytes that can be handed out without growing the heap.
+    fn free(&self) -> usize;
+    /// Bytes mapped for the heap.
+    fn size(&self) -> usize;
+    /// End of the heap region.
+    fn top(&self) -> usize;
+    fn stats(&self) -> HeapStats;
+    /// For allocations made next to the heap, like the large ones, to be counted too.
+    fn stats_mut(&mut self) -> &mut HeapStats;
+
+    /// Free bytes right below `top`, 0 for allocators that can't shrink.
+    fn free_at_top(&self) -> usize {
+        0
+    }
+    /// Takes `by` bytes off the top of the heap, see `FallbackAllocator::shrink`.
+    unsafe fn shrink(&mut self, _by: usize) -> bool {
+        false
+    }
+
+    fn size_classes(&self) -> Option<[SizeClass; fixed_size_block::SIZE_CLASSES]> {
+        None
+    }
+}
+
+/// The allocator `FixedSizeBlockAllocator` falls back to for allocations its block lists
+/// don't serve: one that manages a heap region and can be given more memory at its top,
+/// and maybe give free memory there back.
+pub trait FallbackAllocator {
+    /// Unsafe because the caller must guarantee that the region is mapped and unused. Call only once.
+    unsafe fn init(&mut self, heap_start: usize, heap_size: usize);
+    /// Adds `by` bytes right after the heap top. Unsafe for the same reasons as `init`.
+    unsafe fn extend(&mut self, by: usize);
+    fn allocate(&mut self, layout: Layout) -> Option<NonNull<u8>>;
+    /// Unsafe because `ptr` must come from `allocate` with the same `layout` and be unused.
+    unsafe fn deallocate(&mut self, ptr: NonNull<u8>, layout: Layout);
+    fn used(&self) -> usize;
+    fn free(&self) -> usize;
+    fn size(&self) -> usize;
+    fn top(&self) -> usize;
+    /// Free bytes right below `top`, 0 for allocators that can't shrink.
+    fn free_at_top(&self) -> usize {
+        0
+    }
+    /// Takes `by` bytes off the top of the heap. Returns false and changes nothing if they
+    /// aren't all free, or if the rest of the free memory below can't be kept track of.
+    /// Unsafe because the caller unmaps the bytes afterwards.
+    unsafe fn shrink(&mut self, _by: usize) -> bool {
+        false
+    }
+}
+
+impl FallbackAllocator for linked_list_allocator::Heap {
+    unsafe fn init(&mut self, heap_start: usize, heap_size: usize) {
+        linked_list_allocator::Heap::init(self, heap_start, heap_size)
+    }
+
+    unsafe fn extend(&mut self, by: usize) {
+        linked_list_allocator::Heap::extend(self, by)
+    }
+
+    fn allocate(&mut self, layout: Layout) -> Option<NonNull<u8>> {
+        self.allocate_first_fit(layout).ok()
+    }
+
+    unsafe fn deallocate(&mut self, ptr: NonNull<u8>, layout: Layout) {
+        linked_list_allocator::Heap::deallocate(self, ptr, layout)
+    }
+
+    fn used(&self) -> usize {
+        linked_list_allocator::Heap::used(self)
+    }
+
+    fn free(&self) -> usize {
+        linked_list_allocator::Heap::free(self)
+    }
+
+    fn size(&self) -> usize {
+        linked_list_allocator::Heap::size(self)
+    }
+
+    fn top(&self) -> usize {
+        linked_list_allocator::Heap::top(self)
+    }
+}
+
 pub struct Dummy; // define a dummy global allocator, required to compile the code
 
 unsafe impl GlobalAlloc for Dummy {
@@ -0,0 +1,321 @@
+use super::{FallbackAllocator, HeapAllocator, HeapStats, Locked};
+use alloc::alloc::{GlobalAlloc, Layout};
+use core::ptr::{self, NonNull};
+
+// Binary buddy allocator: the heap is split into blocks whose size is a power of two and
+// which are aligned to their size (counted from the heap start). An allocation takes the
+// smallest free block that fits and splits it in halves ("buddies") until it's just big enough;
+// a freed block is merged with its buddy again as long as the buddy is free too.
+
+const MIN_BLOCK_SIZE: usize = 16; // smallest block, big enough for a list link
+const ORDERS: usize = 21; // block sizes from 16 B up to 16 MiB
+
+fn block_size(order: usize) -> usize {
+    MIN_BLOCK_SIZE << order
+}
+
+pub struct BuddyAllocator {
+    // the free blocks of each order, linked through their first word, 0 ends a list
+    free_lists: [usize; ORDERS],
+    heap_start: usize,
+    heap_size: usize,
+    used: usize, // bytes in handed out blocks
+    stats: HeapStats,
+}
+
+impl BuddyAllocator {
+    // creates an empty BuddyAllocator
+    pub const fn new() -> Self {
+        BuddyAllocator {
+            free_lists: [0; ORDERS],
+            heap_start: 0,
+            heap_size: 0,
+            used: 0,
+            stats: HeapStats::new(),
+        }
+    }
+
+    // initialize the allocator with the given heap bounds
+    // unsafe because caller must ensure that given heap bounds are valid and heap is unused; must be called only once!!!
+    pub unsafe fn init(&mut self, heap_start: usize, heap_size: usize) {
+        self.heap_start = heap_start;
+        self.heap_size = heap_size;
+        self.add_region(heap_start, heap_start + heap_size);
+    }
+
+    // adds `by` bytes right after the end of the heap
+    // unsafe because the caller must ensure the memory is mapped and unused
+    pub unsafe fn extend(&mut self, by: usize) {
+        let top = self.top();
+        self.heap_size += by;
+        self.add_region(top, top + by);
+    }
+
+    pub fn stats(&self) -> HeapStats {
+        self.stats
+    }
+
+    pub fn size(&self) -> usize {
+        self.heap_size
+    }
+
+    pub fn top(&self) -> usize {
+        self.heap_start + self.heap_size
+    }
+
+    // bytes in handed out blocks, including what allocations lose to rounding up
+    pub fn used(&self) -> usize {
+        self.used
+    }
+
+    pub fn free(&self) -> usize {
+        self.heap_size - self.used
+    }
+
+    /// Allocates a block for `layout`, or returns None if no free block is big enough.
+    pub fn allocate(&mut self, layout: Layout) -> Option<NonNull<u8>> {
+        let order = self.order_for(&layout)?;
+        // the smallest free block that is big enough
+        let found = (order..ORDERS).find(|&order| self.free_lists[order] != 0)?;
+        let block = self.pop(found)?;
+        // split it, the upper halves go back on the free lists
+        for smaller in (order..found).rev() {
+            unsafe { self.push(smaller, block + block_size(smaller)) };
+        }
+        self.used += block_size(order);
+        NonNull::new(block as *mut u8)
+    }
+
+    /// Frees a block from `allocate` made for the same `layout`.
+    ///
+    /// Unsafe because the caller must guarantee the block is no longer used.
+    pub unsafe fn deallocate(&mut self, ptr: NonNull<u8>, layout: Layout) {
+        let order = self.order_for(&layout).expect("layout was never allocated");
+        self.used -= block_size(order);
+        self.free_block(order, ptr.as_ptr() as usize);
+    }
+
+    // bytes in the free blocks that are stacked right below the heap top
+    fn free_at_top(&self) -> usize {
+        let mut top = self.top();
+        while let Some((_, block)) = self.free_block_ending_at(top) {
+            top = block;
+        }
+        self.top() - top
+    }
+
+    // takes `by` bytes off the heap top, see FallbackAllocator::shrink
+    unsafe fn shrink(&mut self, by: usize) -> bool {
+        if by == 0 || by > self.free_at_top() {
+            return false;
+        }
+        let new_top = self.top() - by;
+        // take the free blocks above the new top off their lists, the one that
+        // reaches below it is given back split into the part below
+        let mut top = self.top();
+        while top > new_top {
+            let (order, block) = self.free_block_ending_at(top).expect("counted by free_at_top");
+            self.remove(order, block);
+            top = block;
+        }
+        self.heap_size -= by;
+        self.add_region(top, new_top);
+        true
+    }
+
+    // the free block that ends at `end`, with its order
+    fn free_block_ending_at(&self, end: usize) -> Option<(usize, usize)> {
+        (0..ORDERS).find_map(|order| {
+            let block = end.checked_sub(block_size(order)).filter(|&block| block >= self.heap_start)?;
+            let aligned = (block - self.heap_start).is_multiple_of(block_size(order));
+            if aligned && self.is_free(order, block) {
+                Some((order, block))
+            } else {
+                None
+            }
+        })
+    }
+
+    fn is_free(&self, order: usize, block: usize) -> bool {
+        let mut current = self.free_lists[order];
+        while current != 0 {
+            if current == block {
+                return true;
+            }
+            current = unsafe { *(current as *const usize) };
+        }
+        false
+    }
+
+    // order of the smallest block that holds `layout`; blocks are only aligned to their
+    // size counted from the heap start, so larger alignments than the heap start's can't be served
+    fn order_for(&self, layout: &Layout) -> Option<usize> {
+        if layout.align() > 1 << self.heap_start.trailing_zeros().min(63) {
+            return None;
+        }
+        let size = layout.size().max(layout.align()).max(MIN_BLOCK_SIZE).next_power_of_two();
+        let order = (size / MIN_BLOCK_SIZE).trailing_zeros() as usize;
+        if order < ORDERS {
+            Some(order)
+        } else {
+            None
+        }
+    }
+
+    // frees start..end as the largest aligned blocks that fit, merging them with free buddies
+    unsafe fn add_region(&mut self, mut start: usize, end: usize) {
+        start = super::align_up(start, MIN_BLOCK_SIZE);
+        while start + MIN_BLOCK_SIZE <= end {
+            let offset = start - self.heap_start;
+            let order = (0..ORDERS)
+                .rev()
+                .find(|&order| offset.is_multiple_of(block_size(order)) && start + block_size(order) <= end)
+                .unwrap_or(0);
+            self.free_block(order, start);
+            start += block_size(order);
+        }
+    }
+
+    // puts a block on its free list, after merging it with its buddy as long as that one is free
+    unsafe fn free_block(&mut self, mut order: usize, mut block: usize) {
+        while order + 1 < ORDERS {
+            let buddy = self.heap_start + ((block - self.heap_start) ^ block_size(order));
+            if !self.remove(order, buddy) {
+                break;
+            }
+            block = block.min(buddy);
+            order += 1;
+        }
+        self.push(order, block);
+    }
+
+    unsafe fn push(&mut self, order: usize, block: usize) {
+        (block as *mut usize).write(self.free_lists[order]);
+        self.free_lists[order] = block;
+    }
+
+    fn pop(&mut self, order: usize) -> Option<usize> {
+        let block = self.free_lists[order];
+        if block == 0 {
+            return None;
+        }
+        self.free_lists[order] = unsafe { *(block as *const usize) };
+        Some(block)
+    }
+
+    // takes `block` off the free list of `order`, returns false if it isn't on it (so it's in use)
+    unsafe fn remove(&mut self, order: usize, block: usize) -> bool {
+        let mut link: *mut usize = &mut self.free_lists[order];
+        while *link != 0 {
+            if *link == block {
+                *link = *(block as *const usize);
+                return true;
+            }
+            link = *link as *mut usize;
+        }
+        false
+    }
+}
+
+impl FallbackAllocator for BuddyAllocator {
+    unsafe fn init(&mut self, heap_start: usize, heap_size: usize) {
+        BuddyAllocator::init(self, heap_start, heap_size)
+    }
+
+    unsafe fn extend(&mut self, by: usize) {
+        BuddyAllocator::extend(self, by)
+    }
+
+    fn allocate(&mut self, layout: Layout) -> Option<NonNull<u8>> {
+        BuddyAllocator::allocate(self, layout)
+    }
+
+    unsafe fn deallocate(&mut self, ptr: NonNull<u8>, layout: Layout) {
+        BuddyAllocator::deallocate(self, ptr, layout)
+    }
+
+    fn used(&self) -> usize {
+        BuddyAllocator::used(self)
+    }
+
+    fn free(&self) -> usize {
+        BuddyAllocator::free(self)
+    }
+
+    fn size(&self) -> usize {
+        BuddyAllocator::size(self)
+    }
+
+    fn top(&self) -> usize {
+        BuddyAllocator::top(self)
+    }
+
+    fn free_at_top(&self) -> usize {
+        BuddyAllocator::free_at_top(self)
+    }
+
+    unsafe fn shrink(&mut self, by: usize) -> bool {
+        BuddyAllocator::shrink(self, by)
+    }
+}
+
+impl HeapAllocator for BuddyAllocator {
+    const NAME: &'static str = "buddy";
+
+    unsafe fn init(&mut self, heap_start: usize, heap_size: usize) {
+        BuddyAllocator::init(self, heap_start, heap_size)
+    }
+
+    unsafe fn extend(&mut self, by: usize) {
+        BuddyAllocator::extend(self, by)
+    }
+
+    fn used(&self) -> usize {
+        BuddyAllocator::used(self)
+    }
+
+    fn free(&self) -> usize {
+        BuddyAllocator::free(self)
+    }
+
+    fn size(&self) -> usize {
+        BuddyAllocator::size(self)
+    }
+
+    fn top(&self) -> usize {
+        BuddyAllocator::top(self)
+    }
+
+    fn stats(&self) -> HeapStats {
+        self.stats
+    }
+
+    fn stats_mut(&mut self) -> &mut HeapStats {
+        &mut self.stats
+    }
+
+    fn free_at_top(&self) -> usize {
+        BuddyAllocator::free_at_top(self)
+    }
+
+    unsafe fn shrink(&mut self, by: usize) -> bool {
+        BuddyAllocator::shrink(self, by)
+    }
+}
+
+unsafe impl GlobalAlloc for Locked<BuddyAllocator> {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let mut allocator = self.lock();
+        let ptr = allocator.allocate(layout).map_or(ptr::null_mut(), |block| block.as_ptr());
+        if !ptr.is_null() {
+            allocator.stats.record_alloc(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        let mut allocator = self.lock();
+        allocator.stats.record_dealloc(layout.size());
+        allocator.deallocate(NonNull::new(ptr).unwrap(), layout);
+    }
+}
@@ -1,4 +1,4 @@
-use super::{align_up, Locked};
+use super::{align_up, HeapAllocator, HeapStats, Locked};
 use alloc::alloc::{GlobalAlloc, Layout};
 use core::ptr;
 
@@ -11,6 +11,7 @@ pub struct BumpAllocator {
     heap_end: usize,
     next: usize,
     allocations: usize,
+    stats: HeapStats,
 }
 
 impl BumpAllocator {
@@ -21,6 +22,7 @@ impl BumpAllocator {
             heap_end: 0,
             next: 0,
             allocations: 0,
+            stats: HeapStats::new(),
         }
     }
 
@@ -35,6 +37,42 @@ impl BumpAllocator {
     }
 }
 
+impl HeapAllocator for BumpAllocator {
+    const NAME: &'static str = "bump";
+
+    unsafe fn init(&mut self, heap_start: usize, heap_size: usize) {
+        BumpAllocator::init(self, heap_start, heap_size)
+    }
+
+    unsafe fn extend(&mut self, by: usize) {
+        self.heap_end += by;
+    }
+
+    fn used(&self) -> usize {
+        self.next - self.heap_start
+    }
+
+    fn free(&self) -> usize {
+        self.heap_end - self.next
+    }
+
+    fn size(&self) -> usize {
+        self.heap_end - self.heap_start
+    }
+
+    fn top(&self) -> usize {
+        self.heap_end
+    }
+
+    fn stats(&self) -> HeapStats {
+        self.stats
+    }
+
+    fn stats_mut(&mut self) -> &mut HeapStats {
+        &mut self.stats
+    }
+}
+
 unsafe impl GlobalAlloc for Locked<BumpAllocator> {
     unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
         let mut bump = self.lock(); // get a mutable reference
@@ -50,13 +88,15 @@ unsafe impl GlobalAlloc for Locked<BumpAllocator> {
         } else {
             bump.next = alloc_end;
             bump.allocations += 1;
+            bump.stats.record_alloc(layout.size());
             alloc_start as *mut u8
         }
     }
 
-    unsafe fn dealloc(&self, _ptr: *mut u8, _layout: Layout) {
+    unsafe fn dealloc(&self, _ptr: *mut u8, layout: Layout) {
         let mut bump = self.lock(); // get a mutable reference
 
+        bump.stats.record_dealloc(layout.size());
         bump.allocations -= 1;
         if bump.allocations == 0 {
             bump.next = bump.heap_start;
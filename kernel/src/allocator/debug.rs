@@ -0,0 +1,242 @@
+use super::align_up;
+use alloc::alloc::{GlobalAlloc, Layout};
+use alloc::vec::Vec;
+use core::arch::asm;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use core::{mem, ptr, slice};
+use spin::Mutex;
+
+// Debug allocator: wraps another allocator and gives every block a header and two red zones,
+//
+//     [ header | red zone ][ data ][ red zone ]
+//
+// Fresh data is filled with ALLOC_FILL, so reads of uninitialized memory stand out, and freed
+// data with FREE_FILL, so use after free reads something recognisable. The red zones are checked
+// on free and catch writes past either end of the data, the state in the header catches a second
+// free of a block (as long as the memory wasn't handed out again in between).
+// Live blocks are linked through their headers, together with the return addresses of the code
+// that allocated them, for `leaks`. Build with the `debug-alloc` feature to put it in front of the heap.
+
+/// Fresh allocations are filled with this.
+pub const ALLOC_FILL: u8 = 0xcd;
+/// Freed memory is filled with this.
+pub const FREE_FILL: u8 = 0xdd;
+/// The red zones around each block are filled with this.
+pub const RED_ZONE_FILL: u8 = 0xfd;
+const RED_ZONE: usize = 16;
+/// How many return addresses are kept per allocation. The innermost few are liballoc's
+/// (`Box::new`, `RawVec` and friends), the ones after them are the kernel code that allocated.
+pub const CALLERS: usize = 8;
+
+// header states
+const LIVE: usize = 0x11fe_11fe;
+const FREED: usize = 0xdead_dead;
+
+#[repr(C)]
+struct Header {
+    prev: usize, // neighbours in the live list, 0 at the ends
+    next: usize,
+    size: usize, // as requested
+    sequence: u64,
+    callers: [usize; CALLERS],
+    state: usize,
+    red_zone: [u8; RED_ZONE], // right in front of the data
+}
+
+/// A block that was allocated and not freed yet.
+#[derive(Debug, Clone, Copy)]
+pub struct LiveAllocation {
+    pub address: usize,
+    pub size: usize,
+    pub sequence: u64, // counts the allocations, a higher one was made later
+    pub callers: [usize; CALLERS], // return addresses, innermost first, 0 where the stack ended
+}
+
+struct LiveList {
+    head: usize, // newest header, 0 if none
+    count: usize,
+    sequence: u64,
+}
+
+pub struct DebugAllocator<A> {
+    inner: A,
+    live: Mutex<LiveList>,
+}
+
+impl<A> DebugAllocator<A> {
+    pub const fn new(inner: A) -> Self {
+        DebugAllocator {
+            inner,
+            live: Mutex::new(LiveList {
+                head: 0,
+                count: 0,
+                sequence: 0,
+            }),
+        }
+    }
+
+    /// The allocator the blocks come from.
+    pub const fn inner(&self) -> &A {
+        &self.inner
+    }
+
+    /// The live allocations, oldest first.
+    pub fn live_allocations(&self) -> Vec<LiveAllocation> {
+        // allocating with the list locked would deadlock, so make room first (this Vec is one more)
+        let count = self.live.lock().count;
+        let mut allocations = Vec::with_capacity(count + 16);
+        let live = self.live.lock();
+        let mut header = live.head;
+        while header != 0 && allocations.len() < allocations.capacity() {
+            let entry = unsafe { &*(header as *const Header) };
+            let address = header + mem::size_of::<Header>();
+            if address != allocations.as_ptr() as usize {
+                allocations.push(LiveAllocation {
+                    address,
+                    size: entry.size,
+                    sequence: entry.sequence,
+                    callers: entry.callers,
+                });
+            }
+            header = entry.next;
+        }
+        drop(live);
+        allocations.reverse();
+        allocations
+    }
+}
+
+// where the data starts in the block, and the layout of the whole block
+fn block_layout(layout: &Layout) -> Option<(usize, Layout)> {
+    let align = layout.align().max(mem::align_of::<Header>());
+    let offset = align_up(mem::size_of::<Header>(), align);
+    let size = offset.checked_add(layout.size())?.checked_add(RED_ZONE)?;
+    Some((offset, Layout::from_size_align(size, align).ok()?))
+}
+
+// end of the boot stack, 0 until `record_stack_top` ran
+static STACK_TOP: AtomicUsize = AtomicUsize::new(0);
+// the bootloader's kernel stack is 80 pages, a stack pointer further down is on another stack
+const MAX_STACK_SIZE: usize = 80 * 4096;
+
+/// Remembers where the boot stack ends, `callers` doesn't read past it. Called by `kernel::init`.
+#[inline(never)]
+pub fn record_stack_top() {
+    let stack: usize;
+    unsafe { asm!("mov {}, rsp", out(reg) stack, options(nomem, nostack, preserves_flags)) };
+    // the stack may go on above, but the end of this page is known to be mapped
+    STACK_TOP.store(align_up(stack + 1, 4096), Ordering::Relaxed);
+}
+
+// return addresses of the functions up the stack, read through the frame pointers; the innermost
+// `skip` frames are left out. Only builds with frame pointers (the debug-alloc profile) have a chain
+// to follow, and the walk stays on the boot stack between rsp and its top, which is all mapped.
+#[inline(always)]
+fn callers(skip: usize) -> [usize; CALLERS] {
+    let mut callers = [0; CALLERS];
+    if !cfg!(frame_pointers) {
+        return callers;
+    }
+    let (mut frame, stack): (usize, usize);
+    unsafe {
+        asm!("mov {}, rbp", "mov {}, rsp", out(reg) frame, out(reg) stack, options(nomem, nostack, preserves_flags))
+    };
+    let top = STACK_TOP.load(Ordering::Relaxed);
+    if stack >= top || top - stack > MAX_STACK_SIZE {
+        return callers; // too early, or on an interrupt stack
+    }
+
+    // a frame record is the saved rbp and the return address; each one has to be further up the
+    // stack than the last, anything else is the end of the chain (or code without a frame)
+    let mut lowest = stack;
+    for index in 0..skip + CALLERS {
+        if frame < lowest || frame > top - 2 * mem::size_of::<usize>() || !frame.is_multiple_of(mem::align_of::<usize>()) {
+            break;
+        }
+        let (saved_frame, return_address) = unsafe { (*(frame as *const usize), *((frame + 8) as *const usize)) };
+        if index >= skip {
+            callers[index - skip] = return_address;
+        }
+        lowest = frame + 2 * mem::size_of::<usize>();
+        frame = saved_frame;
+    }
+    callers
+}
+
+unsafe impl<A: GlobalAlloc> GlobalAlloc for DebugAllocator<A> {
+    #[inline(never)]
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let (offset, block) = match block_layout(&layout) {
+            Some(block_layout) => block_layout,
+            None => return ptr::null_mut(),
+        };
+        let block = self.inner.alloc(block);
+        if block.is_null() {
+            return block;
+        }
+        let data = block.add(offset);
+        data.write_bytes(ALLOC_FILL, layout.size());
+        data.add(layout.size()).write_bytes(RED_ZONE_FILL, RED_ZONE);
+
+        // skip the frame of the allocator shim, the first interesting caller is the one after it
+        let callers = callers(1);
+        let header = data.sub(mem::size_of::<Header>()) as *mut Header;
+        let mut live = self.live.lock();
+        live.sequence += 1;
+        header.write(Header {
+            prev: 0,
+            next: live.head,
+            size: layout.size(),
+            sequence: live.sequence,
+            callers,
+            state: LIVE,
+            red_zone: [RED_ZONE_FILL; RED_ZONE],
+        });
+        if live.head != 0 {
+            (*(live.head as *mut Header)).prev = header as usize;
+        }
+        live.head = header as usize;
+        live.count += 1;
+        data
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        let (offset, block) = block_layout(&layout).expect("layout was never allocated");
+        let header = &mut *(ptr.sub(mem::size_of::<Header>()) as *mut Header);
+
+        // all checks before locking the list, a caught panic would leave it locked
+        match header.state {
+            LIVE => {}
+            FREED => panic!("double free of {:p} ({} bytes)", ptr, layout.size()),
+            _ => panic!("free of {:p}, which isn't a live allocation or its header was overwritten", ptr),
+        }
+        assert_eq!(header.size, layout.size(), "{:p} freed with another size than it was allocated with", ptr);
+        let front = header.red_zone.iter().all(|&byte| byte == RED_ZONE_FILL);
+        let back = slice::from_raw_parts(ptr.add(layout.size()), RED_ZONE).iter().all(|&byte| byte == RED_ZONE_FILL);
+        if !front || !back {
+            panic!(
+                "red zone {} {:p} ({} bytes) overwritten, allocated from {:#x?}",
+                if front { "after" } else { "before" },
+                ptr,
+                layout.size(),
+                header.callers
+            );
+        }
+
+        let mut live = self.live.lock();
+        if header.prev != 0 {
+            (*(header.prev as *mut Header)).next = header.next;
+        } else {
+            live.head = header.next;
+        }
+        if header.next != 0 {
+            (*(header.next as *mut Header)).prev = header.prev;
+        }
+        live.count -= 1;
+        drop(live);
+
+        header.state = FREED;
+        ptr.write_bytes(FREE_FILL, layout.size());
+        self.inner.dealloc(ptr.sub(offset), block);
+    }
+}
@@ -1,4 +1,4 @@
-use super::Locked;
+use super::{linked_list::LinkedListAllocator, FallbackAllocator, HeapAllocator, HeapStats, Locked};
 use alloc::alloc::{GlobalAlloc, Layout};
 use core::{
     mem,
@@ -9,6 +9,13 @@ use core::{
 // sizes must be power of 2 because they are also used as the block alignment (alignments must be always powers of 2)
 const BLOCK_SIZES: &[usize] = &[8, 16, 32, 64, 128, 256, 512, 1024, 2048];
 
+/// Number of block sizes.
+pub const SIZE_CLASSES: usize = BLOCK_SIZES.len();
+
+// free blocks of a size class beyond this many bytes go back to the fallback allocator,
+// so the heap can shrink again after a burst of small allocations
+const MAX_PARKED_BYTES: usize = 16 * 1024;
+
 // choose an appropriate block size for the given layout
 // returns an index into the BLOCK_SIZES array
 fn list_index(layout: &Layout) -> Option<usize> {
@@ -20,18 +27,41 @@ struct ListNode {
     next: Option<&'static mut ListNode>,
 }
 
-pub struct FixedSizeBlockAllocator {
+/// Block counts of one block size.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SizeClass {
+    pub block_size: usize,
+    pub live: usize, // handed out
+    pub free: usize, // parked in the list, waiting for reuse
+}
+
+/// Serves small allocations from per-size block lists, everything else (and new blocks)
+/// from the fallback allocator `F`: `LinkedListAllocator` by default, or `BuddyAllocator`.
+pub struct FixedSizeBlockAllocator<F = LinkedListAllocator> {
     list_heads: [Option<&'static mut ListNode>; BLOCK_SIZES.len()],
-    fallback_allocator: linked_list_allocator::Heap,
+    fallback_allocator: F,
+    stats: HeapStats,
+    live_blocks: [usize; SIZE_CLASSES],
+    free_blocks: [usize; SIZE_CLASSES],
 }
 
 impl FixedSizeBlockAllocator {
     // Creates an empty FixedSizeBlockAllocator.
     pub const fn new() -> Self {
+        Self::with_fallback(LinkedListAllocator::new())
+    }
+}
+
+impl<F: FallbackAllocator> FixedSizeBlockAllocator<F> {
+    // Creates an empty FixedSizeBlockAllocator on top of an empty `fallback_allocator`.
+    pub const fn with_fallback(fallback_allocator: F) -> Self {
         const EMPTY: Option<&'static mut ListNode> = None;
         FixedSizeBlockAllocator {
             list_heads: [EMPTY; BLOCK_SIZES.len()],
-            fallback_allocator: linked_list_allocator::Heap::empty(),
+            fallback_allocator,
+            stats: HeapStats::new(),
+            live_blocks: [0; SIZE_CLASSES],
+            free_blocks: [0; SIZE_CLASSES],
         }
     }
 
@@ -42,23 +72,103 @@ impl FixedSizeBlockAllocator {
         self.fallback_allocator.init(heap_start, heap_size);
     }
 
+    // bytes handed out by the fallback allocator (blocks parked in the lists count as used)
+    pub fn used(&self) -> usize {
+        self.fallback_allocator.used()
+    }
+
+    // bytes the fallback allocator can still hand out without growing
+    pub fn free(&self) -> usize {
+        self.fallback_allocator.free()
+    }
+
+    // bytes mapped for the heap, grows on demand
+    pub fn size(&self) -> usize {
+        self.fallback_allocator.size()
+    }
+
+    pub fn stats(&self) -> HeapStats {
+        self.stats
+    }
+
+    pub fn size_classes(&self) -> [SizeClass; SIZE_CLASSES] {
+        let mut classes = [SizeClass { block_size: 0, live: 0, free: 0 }; SIZE_CLASSES];
+        for (index, class) in classes.iter_mut().enumerate() {
+            *class = SizeClass {
+                block_size: BLOCK_SIZES[index],
+                live: self.live_blocks[index],
+                free: self.free_blocks[index],
+            };
+        }
+        classes
+    }
+
     // allocates using the fallback allocator
     fn fallback_alloc(&mut self, layout: Layout) -> *mut u8 {
-        match self.fallback_allocator.allocate_first_fit(layout) {
-            Ok(ptr) => ptr.as_ptr(),
-            Err(_) => ptr::null_mut(),
+        match self.fallback_allocator.allocate(layout) {
+            Some(ptr) => ptr.as_ptr(),
+            None => ptr::null_mut(),
         }
     }
 }
 
-unsafe impl GlobalAlloc for Locked<FixedSizeBlockAllocator> {
+impl<F: FallbackAllocator> HeapAllocator for FixedSizeBlockAllocator<F> {
+    const NAME: &'static str = "fixed-block";
+
+    unsafe fn init(&mut self, heap_start: usize, heap_size: usize) {
+        FixedSizeBlockAllocator::init(self, heap_start, heap_size)
+    }
+
+    unsafe fn extend(&mut self, by: usize) {
+        self.fallback_allocator.extend(by)
+    }
+
+    fn used(&self) -> usize {
+        FixedSizeBlockAllocator::used(self)
+    }
+
+    fn free(&self) -> usize {
+        FixedSizeBlockAllocator::free(self)
+    }
+
+    fn size(&self) -> usize {
+        FixedSizeBlockAllocator::size(self)
+    }
+
+    fn top(&self) -> usize {
+        self.fallback_allocator.top()
+    }
+
+    fn stats(&self) -> HeapStats {
+        self.stats
+    }
+
+    fn stats_mut(&mut self) -> &mut HeapStats {
+        &mut self.stats
+    }
+
+    fn free_at_top(&self) -> usize {
+        self.fallback_allocator.free_at_top()
+    }
+
+    unsafe fn shrink(&mut self, by: usize) -> bool {
+        self.fallback_allocator.shrink(by)
+    }
+
+    fn size_classes(&self) -> Option<[SizeClass; SIZE_CLASSES]> {
+        Some(FixedSizeBlockAllocator::size_classes(self))
+    }
+}
+
+unsafe impl<F: FallbackAllocator> GlobalAlloc for Locked<FixedSizeBlockAllocator<F>> {
     unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
         let mut allocator = self.lock();
-        match list_index(&layout) {
+        let ptr = match list_index(&layout) {
             Some(index) => {
-                match allocator.list_heads[index].take() {
+                let ptr = match allocator.list_heads[index].take() {
                     Some(node) => {
                         allocator.list_heads[index] = node.next.take();
+                        allocator.free_blocks[index] -= 1;
                         node as *mut ListNode as *mut u8
                     }
                     None => {
@@ -69,15 +179,36 @@ unsafe impl GlobalAlloc for Locked<FixedSizeBlockAllocator> {
                         let layout = Layout::from_size_align(block_size, block_align).unwrap();
                         allocator.fallback_alloc(layout)
                     }
+                };
+                if !ptr.is_null() {
+                    allocator.live_blocks[index] += 1;
+                }
+                ptr
+            }
+            None => {
+                let ptr = allocator.fallback_alloc(layout);
+                if !ptr.is_null() {
+                    allocator.stats.fallback_allocations += 1;
                 }
+                ptr
             }
-            None => allocator.fallback_alloc(layout),
+        };
+        if !ptr.is_null() {
+            allocator.stats.record_alloc(layout.size());
         }
+        ptr
     }
 
     unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
         let mut allocator = self.lock();
+        allocator.stats.record_dealloc(layout.size());
         match list_index(&layout) {
+            Some(index) if (allocator.free_blocks[index] + 1) * BLOCK_SIZES[index] > MAX_PARKED_BYTES => {
+                let block_size = BLOCK_SIZES[index];
+                let block = Layout::from_size_align(block_size, block_size).unwrap();
+                allocator.fallback_allocator.deallocate(NonNull::new(ptr).unwrap(), block);
+                allocator.live_blocks[index] -= 1;
+            }
             Some(index) => {
                 let new_node = ListNode {
                     next: allocator.list_heads[index].take(),
@@ -88,6 +219,8 @@ unsafe impl GlobalAlloc for Locked<FixedSizeBlockAllocator> {
                 let new_node_ptr = ptr as *mut ListNode;
                 new_node_ptr.write(new_node);
                 allocator.list_heads[index] = Some(&mut *new_node_ptr);
+                allocator.live_blocks[index] -= 1;
+                allocator.free_blocks[index] += 1;
             }
             None => {
                 let ptr = NonNull::new(ptr).unwrap();
@@ -0,0 +1,82 @@
+use super::{grow_heap, large, shrink_heap, HeapAllocator, Locked};
+use alloc::alloc::{GlobalAlloc, Layout};
+use core::ptr;
+
+// The kernel heap: one of the plain allocators in a region that grows by mapping pages at its
+// top when it runs full and gives them back once enough of the top is free again, and large
+// allocations that get pages of their own instead of a place in the region (see `large`).
+// Only the global allocator is wrapped in this, so allocators made for a private arena never
+// touch the page tables.
+
+pub struct GrowableHeap<A> {
+    heap: Locked<A>,
+}
+
+impl<A> GrowableHeap<A> {
+    pub const fn new(heap: A) -> Self {
+        GrowableHeap { heap: Locked::new(heap) }
+    }
+
+    pub fn lock(&self) -> spin::MutexGuard<'_, A> {
+        self.heap.lock()
+    }
+}
+
+unsafe impl<A: HeapAllocator> GlobalAlloc for GrowableHeap<A>
+where
+    Locked<A>: GlobalAlloc,
+{
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        if large::is_large(&layout) {
+            let ptr = large::alloc(&layout);
+            if !ptr.is_null() {
+                self.lock().stats_mut().record_alloc(layout.size());
+            }
+            return ptr;
+        }
+
+        let ptr = self.heap.alloc(layout);
+        if !ptr.is_null() {
+            return ptr;
+        }
+        // out of heap: map more pages at the top and try once more
+        {
+            let mut heap = self.lock();
+            match grow_heap(heap.top(), heap.size(), &layout) {
+                Some(size) => heap.extend(size),
+                None => return ptr::null_mut(),
+            }
+        }
+        self.heap.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        if large::is_large(&layout) {
+            self.lock().stats_mut().record_dealloc(layout.size());
+            large::dealloc(ptr, &layout);
+            return;
+        }
+        self.heap.dealloc(ptr, layout);
+        shrink_heap(&mut *self.lock());
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_layout = Layout::from_size_align_unchecked(new_size, layout.align());
+        if !large::is_large(&layout) && !large::is_large(&new_layout) {
+            // the allocator may resize in place, or move it within the heap if that has room
+            let new_ptr = self.heap.realloc(ptr, layout, new_size);
+            if !new_ptr.is_null() {
+                shrink_heap(&mut *self.lock());
+                return new_ptr;
+            }
+        }
+
+        // into or out of its own pages, or the heap has to grow first
+        let new_ptr = self.alloc(new_layout);
+        if !new_ptr.is_null() {
+            ptr::copy_nonoverlapping(ptr, new_ptr, layout.size().min(new_size));
+            self.dealloc(ptr, layout);
+        }
+        new_ptr
+    }
+}
@@ -0,0 +1,86 @@
+use super::{align_up, map_pages, unmap_pages, PAGE_SIZE};
+use alloc::alloc::Layout;
+use core::ptr;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use spin::Mutex;
+
+// Large allocations don't go into the heap: each one gets pages of its own in a separate
+// address range, which are unmapped when it's freed, so its frames are given back right away
+// instead of staying in the heap for good. Addresses aren't reused, the range is big enough.
+
+/// Allocations of at least this many bytes get pages of their own.
+pub const LARGE_ALLOCATION: usize = 64 * 1024;
+
+const LARGE_START: usize = 0x_5555_0000_0000;
+const LARGE_END: usize = LARGE_START + (1 << 39); // 512 GiB
+
+static NEXT: AtomicUsize = AtomicUsize::new(LARGE_START); // where the next allocation goes
+static MAPPED: AtomicUsize = AtomicUsize::new(0); // bytes mapped for live allocations
+
+// freed allocations (start, size) that are still mapped because the page tables were busy,
+// unmapped on the next large allocation or free; (0, 0) is an empty slot
+const PENDING_SLOTS: usize = 16;
+static PENDING: Mutex<[(usize, usize); PENDING_SLOTS]> = Mutex::new([(0, 0); PENDING_SLOTS]);
+
+/// Whether `layout` is served by `alloc` instead of the heap.
+pub fn is_large(layout: &Layout) -> bool {
+    layout.size() >= LARGE_ALLOCATION && layout.align() <= PAGE_SIZE
+}
+
+/// Bytes mapped for live large allocations.
+pub fn mapped_bytes() -> usize {
+    MAPPED.load(Ordering::Relaxed)
+}
+
+/// Maps fresh pages for `layout`. Returns null if there are no frames left.
+pub fn alloc(layout: &Layout) -> *mut u8 {
+    unmap_pending();
+    let size = align_up(layout.size(), PAGE_SIZE);
+    // one unmapped page after every allocation catches overruns
+    let start = NEXT.fetch_add(size + PAGE_SIZE, Ordering::Relaxed);
+    if start + size > LARGE_END {
+        return ptr::null_mut();
+    }
+    match map_pages(start, size) {
+        Ok(()) => {
+            MAPPED.fetch_add(size, Ordering::Relaxed);
+            start as *mut u8
+        }
+        Err(_) => ptr::null_mut(),
+    }
+}
+
+/// Unmaps an allocation from `alloc` and gives its frames back.
+pub fn dealloc(ptr: *mut u8, layout: &Layout) {
+    unmap_pending();
+    let size = align_up(layout.size(), PAGE_SIZE);
+    if unmap(ptr as usize, size) {
+        return;
+    }
+    // the page tables are busy: try again later
+    let mut pending = PENDING.lock();
+    match pending.iter_mut().find(|slot| slot.1 == 0) {
+        Some(slot) => *slot = (ptr as usize, size),
+        None => {
+            drop(pending);
+            crate::log_warn!("large allocation at {:p} ({} KiB) stays mapped, the page tables are busy", ptr, size / 1024);
+        }
+    }
+}
+
+fn unmap(start: usize, size: usize) -> bool {
+    let unmapped = unmap_pages(start, size);
+    if unmapped {
+        MAPPED.fetch_sub(size, Ordering::Relaxed);
+    }
+    unmapped
+}
+
+fn unmap_pending() {
+    let mut pending = PENDING.lock();
+    for slot in pending.iter_mut().filter(|slot| slot.1 != 0) {
+        if unmap(slot.0, slot.1) {
+            *slot = (0, 0);
+        }
+    }
+}
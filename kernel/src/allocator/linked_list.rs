@@ -1,6 +1,9 @@
-use super::{align_up, Locked};
+use super::{align_up, FallbackAllocator, HeapAllocator, HeapStats, Locked};
 use alloc::alloc::{GlobalAlloc, Layout};
-use core::{mem, ptr};
+use core::{
+    mem,
+    ptr::{self, NonNull},
+};
 
 
 struct ListNode {
@@ -26,6 +29,9 @@ impl ListNode {
 
 pub struct LinkedListAllocator {
     head: ListNode,
+    heap_top: usize,
+    heap_size: usize,
+    stats: HeapStats,
 }
 
 impl LinkedListAllocator {
@@ -33,27 +39,185 @@ impl LinkedListAllocator {
     pub const fn new() -> Self {
         LinkedListAllocator {
             head: ListNode::new(0),
+            heap_top: 0,
+            heap_size: 0,
+            stats: HeapStats::new(),
         }
     }
 
     // initialize LinkedListAllocator with given heap bounds
     // unsafe because caller must ensure that given heap bounds are valid and heap is unused; must be called only once!!!
     pub unsafe fn init(&mut self, heap_start: usize, heap_size: usize) {
+        self.heap_top = heap_start + heap_size;
+        self.heap_size = heap_size;
         self.add_free_region(heap_start, heap_size);
     }
 
-    // adds the given memory region to the front of the list.
+    // adds `by` bytes right after the end of the heap as a new free region
+    // unsafe because the caller must ensure the memory is mapped and unused
+    pub unsafe fn extend(&mut self, by: usize) {
+        self.add_free_region(self.heap_top, by);
+        self.heap_top += by;
+        self.heap_size += by;
+    }
+
+    pub fn stats(&self) -> HeapStats {
+        self.stats
+    }
+
+    // bytes in the free list
+    pub fn free(&self) -> usize {
+        self.regions().map(|region| region.size).sum()
+    }
+
+    // size of the biggest free region, the largest allocation that can still succeed
+    pub fn largest_free_region(&self) -> usize {
+        self.regions().map(|region| region.size).max().unwrap_or(0)
+    }
+
+    pub fn size(&self) -> usize {
+        self.heap_size
+    }
+
+    pub fn top(&self) -> usize {
+        self.heap_top
+    }
+
+    /// Allocates a region for `layout`, or returns None if no free region is big enough.
+    pub fn allocate(&mut self, layout: Layout) -> Option<NonNull<u8>> {
+        // perform layout adjustments
+        let (size, align) = Self::size_align(layout);
+        let (region, alloc_start) = self.find_region(size, align)?;
+        let alloc_end = alloc_start.checked_add(size).expect("overflow");
+        let excess_size = region.end_addr() - alloc_end;
+        // the gap alignment left in front of the allocation (this overwrites the node)
+        let padding = alloc_start - region.start_addr();
+        unsafe {
+            if padding > 0 {
+                self.add_free_region(region.start_addr(), padding);
+            }
+            if excess_size > 0 {
+                self.add_free_region(alloc_end, excess_size);
+            }
+        }
+        NonNull::new(alloc_start as *mut u8)
+    }
+
+    /// Frees a region from `allocate` made for the same `layout`.
+    ///
+    /// Unsafe because the caller must guarantee the region is no longer used.
+    pub unsafe fn deallocate(&mut self, ptr: NonNull<u8>, layout: Layout) {
+        let (size, _) = Self::size_align(layout);
+        self.add_free_region(ptr.as_ptr() as usize, size);
+    }
+
+    // size of the free region that ends at the heap top, 0 if there is none
+    fn free_at_top(&self) -> usize {
+        self.regions().last().filter(|region| region.end_addr() == self.heap_top).map_or(0, |region| region.size)
+    }
+
+    // takes `by` bytes off the free region at the heap top, see FallbackAllocator::shrink
+    unsafe fn shrink(&mut self, by: usize) -> bool {
+        let free = self.free_at_top();
+        if by == 0 || by > free || (by < free && free - by < mem::size_of::<ListNode>()) {
+            return false;
+        }
+
+        // the last region is the one at the top
+        let mut current = &mut self.head;
+        while current.next.as_ref().is_some_and(|next| next.next.is_some()) {
+            current = current.next.as_mut().unwrap();
+        }
+        if by == free {
+            current.next = None;
+        } else {
+            current.next.as_mut().unwrap().size -= by;
+        }
+        self.heap_top -= by;
+        self.heap_size -= by;
+        true
+    }
+
+    fn regions(&self) -> impl Iterator<Item = &ListNode> {
+        core::iter::successors(self.head.next.as_deref(), |region| region.next.as_deref())
+    }
+
+    // adds the given memory region to the list, which is sorted by address, and merges it
+    // with the free regions right before and after it, so freed neighbours become one region again
     unsafe fn add_free_region(&mut self, addr: usize, size: usize) {
         // ensure that the freed region is capable of holding ListNode
         assert_eq!(align_up(addr, mem::align_of::<ListNode>()), addr);
         assert!(size >= mem::size_of::<ListNode>());
 
-        // create a new list node and append it at the start of the list
-        let mut node = ListNode::new(size);
-        node.next = self.head.next.take();
-        let node_ptr = addr as *mut ListNode;
-        node_ptr.write(node);
-        self.head.next = Some(&mut *node_ptr)
+        // the last region before addr, or the head
+        let head = &self.head as *const ListNode as usize;
+        let mut current = &mut self.head;
+        while current.next.as_ref().is_some_and(|next| next.start_addr() < addr) {
+            current = current.next.as_mut().unwrap();
+        }
+        assert!(current.start_addr() == head || current.end_addr() <= addr, "heap region {:#x} freed twice", addr);
+
+        // merge with the following region
+        let mut size = size;
+        if let Some(following) = current.next.take() {
+            assert!(addr + size <= following.start_addr(), "heap region {:#x} freed twice", addr);
+            if addr + size == following.start_addr() {
+                size += following.size;
+                current.next = following.next.take();
+            } else {
+                current.next = Some(following);
+            }
+        }
+
+        // merge with the preceding region, or link in a new node after it
+        if current.start_addr() != head && current.end_addr() == addr {
+            current.size += size;
+        } else {
+            let mut node = ListNode::new(size);
+            node.next = current.next.take();
+            let node_ptr = addr as *mut ListNode;
+            node_ptr.write(node);
+            current.next = Some(&mut *node_ptr)
+        }
+    }
+
+    // resizes the allocation at addr from old_size to new_size bytes (both from size_align)
+    // without moving it, returns false if that isn't possible
+    unsafe fn resize_in_place(&mut self, addr: usize, old_size: usize, new_size: usize) -> bool {
+        if new_size <= old_size {
+            // shrink: the tail goes back to the list, unless it can't hold a ListNode
+            let tail = old_size - new_size;
+            if tail >= mem::size_of::<ListNode>() {
+                self.add_free_region(addr + new_size, tail);
+            }
+            return tail == 0 || tail >= mem::size_of::<ListNode>();
+        }
+
+        // grow: only into a free region that starts right at the end of the allocation
+        let end = addr + old_size;
+        let mut current = &mut self.head;
+        while current.next.as_ref().is_some_and(|next| next.start_addr() < end) {
+            current = current.next.as_mut().unwrap();
+        }
+        let following = match current.next.take() {
+            Some(following) if following.start_addr() == end => following,
+            other => {
+                current.next = other;
+                return false;
+            }
+        };
+
+        let needed = new_size - old_size;
+        if following.size < needed || (following.size > needed && following.size - needed < mem::size_of::<ListNode>()) {
+            current.next = Some(following);
+            return false;
+        }
+        let excess_size = following.size - needed;
+        current.next = following.next.take();
+        if excess_size > 0 {
+            self.add_free_region(addr + new_size, excess_size);
+        }
+        true
     }
 
     // looks for a free region with the given size and alignment and removes it from the list
@@ -63,7 +227,7 @@ impl LinkedListAllocator {
         let mut current = &mut self.head;
         // look for a large enough memory region in linked list
         while let Some(ref mut region) = current.next {
-            if let Ok(alloc_start) = Self::alloc_from_region(&region, size, align) {
+            if let Ok(alloc_start) = Self::alloc_from_region(region, size, align) {
                 // region suitable for allocation -> remove node from list
                 let next = region.next.take();
                 let ret = Some((current.next.take().unwrap(), alloc_start));
@@ -82,7 +246,11 @@ impl LinkedListAllocator {
     // try to use the given region for an allocation with given size and alignment
     // returns the allocation start address on success
     fn alloc_from_region(region: &ListNode, size: usize, align: usize) -> Result<usize, ()> {
-        let alloc_start = align_up(region.start_addr(), align);
+        let mut alloc_start = align_up(region.start_addr(), align);
+        if alloc_start > region.start_addr() && alloc_start - region.start_addr() < mem::size_of::<ListNode>() {
+            // the gap in front goes back to the list, so it has to hold a ListNode too
+            alloc_start = align_up(region.start_addr() + mem::size_of::<ListNode>(), align);
+        }
         let alloc_end = alloc_start.checked_add(size).ok_or(())?;
 
         if alloc_end > region.end_addr() {
@@ -111,28 +279,125 @@ impl LinkedListAllocator {
     }
 }
 
+impl FallbackAllocator for LinkedListAllocator {
+    unsafe fn init(&mut self, heap_start: usize, heap_size: usize) {
+        LinkedListAllocator::init(self, heap_start, heap_size)
+    }
+
+    unsafe fn extend(&mut self, by: usize) {
+        LinkedListAllocator::extend(self, by)
+    }
+
+    fn allocate(&mut self, layout: Layout) -> Option<NonNull<u8>> {
+        LinkedListAllocator::allocate(self, layout)
+    }
+
+    unsafe fn deallocate(&mut self, ptr: NonNull<u8>, layout: Layout) {
+        LinkedListAllocator::deallocate(self, ptr, layout)
+    }
+
+    fn used(&self) -> usize {
+        self.heap_size - LinkedListAllocator::free(self)
+    }
+
+    fn free(&self) -> usize {
+        LinkedListAllocator::free(self)
+    }
+
+    fn size(&self) -> usize {
+        self.heap_size
+    }
+
+    fn top(&self) -> usize {
+        self.heap_top
+    }
+
+    fn free_at_top(&self) -> usize {
+        LinkedListAllocator::free_at_top(self)
+    }
+
+    unsafe fn shrink(&mut self, by: usize) -> bool {
+        LinkedListAllocator::shrink(self, by)
+    }
+}
+
+impl HeapAllocator for LinkedListAllocator {
+    const NAME: &'static str = "linked-list";
+
+    unsafe fn init(&mut self, heap_start: usize, heap_size: usize) {
+        LinkedListAllocator::init(self, heap_start, heap_size)
+    }
+
+    unsafe fn extend(&mut self, by: usize) {
+        LinkedListAllocator::extend(self, by)
+    }
+
+    fn used(&self) -> usize {
+        self.heap_size - LinkedListAllocator::free(self)
+    }
+
+    fn free(&self) -> usize {
+        LinkedListAllocator::free(self)
+    }
+
+    fn size(&self) -> usize {
+        self.heap_size
+    }
+
+    fn top(&self) -> usize {
+        self.heap_top
+    }
+
+    fn stats(&self) -> HeapStats {
+        self.stats
+    }
+
+    fn stats_mut(&mut self) -> &mut HeapStats {
+        &mut self.stats
+    }
+
+    fn free_at_top(&self) -> usize {
+        LinkedListAllocator::free_at_top(self)
+    }
+
+    unsafe fn shrink(&mut self, by: usize) -> bool {
+        LinkedListAllocator::shrink(self, by)
+    }
+}
+
 unsafe impl GlobalAlloc for Locked<LinkedListAllocator> {
     unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
-        // perform layout adjustments
-        let (size, align) = LinkedListAllocator::size_align(layout);
         let mut allocator = self.lock();
-
-        if let Some((region, alloc_start)) = allocator.find_region(size, align) {
-            let alloc_end = alloc_start.checked_add(size).expect("overflow");
-            let excess_size = region.end_addr() - alloc_end;
-            if excess_size > 0 {
-                allocator.add_free_region(alloc_end, excess_size);
-            }
-            alloc_start as *mut u8
-        } else {
-            ptr::null_mut()
+        let ptr = allocator.allocate(layout).map_or(ptr::null_mut(), |ptr| ptr.as_ptr());
+        if !ptr.is_null() {
+            allocator.stats.record_alloc(layout.size());
         }
+        ptr
     }
 
     unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
-        // perform layout adjustments
-        let (size, _) = LinkedListAllocator::size_align(layout);
+        let mut allocator = self.lock();
+        allocator.stats.record_dealloc(layout.size());
+        allocator.deallocate(NonNull::new(ptr).unwrap(), layout);
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_layout = Layout::from_size_align_unchecked(new_size, layout.align());
+        let (old_padded, _) = LinkedListAllocator::size_align(layout);
+        let (new_padded, _) = LinkedListAllocator::size_align(new_layout);
+        let mut allocator = self.lock();
+        if allocator.resize_in_place(ptr as usize, old_padded, new_padded) {
+            allocator.stats.record_resize(layout.size(), new_size);
+            return ptr;
+        }
+        drop(allocator);
 
-        self.lock().add_free_region(ptr as usize, size)
+        // can't stay where it is: move it
+        let new_ptr = self.alloc(new_layout);
+        if !new_ptr.is_null() {
+            ptr::copy_nonoverlapping(ptr, new_ptr, layout.size().min(new_size));
+            self.dealloc(ptr, layout);
+        }
+        new_ptr
     }
 }
\ No newline at end of file
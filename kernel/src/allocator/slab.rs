@@ -0,0 +1,341 @@
+use super::align_up;
+use crate::memory::{self, FRAME_ALLOCATOR};
+use alloc::vec::Vec;
+use core::mem;
+use core::ops::{Deref, DerefMut};
+use core::ptr::{self, NonNull};
+use core::sync::atomic::{AtomicBool, Ordering};
+use spin::Mutex;
+use x86_64::structures::paging::{FrameAllocator, FrameDeallocator, PhysFrame};
+use x86_64::VirtAddr;
+
+// Slab caches: a cache hands out objects of one size, carved out of whole frames ("slabs")
+// taken from the frame allocator and reached through the physical memory mapping.
+// Each slab starts with a header, followed by its objects; the free list link of an object
+// sits right after it, so an object keeps whatever the constructor put into it while it's free.
+// Slabs move between a full, a partial and an empty list, and empty slabs go back to the
+// frame allocator, one is kept per cache for the next allocation.
+
+const SLAB_SIZE: usize = 4096;
+/// Objects have to be at most this big, so a slab holds a few of them.
+pub const MAX_OBJECT_SIZE: usize = 1024;
+
+// at the start of every slab
+struct Slab {
+    prev: usize, // neighbours in the cache list the slab is on, 0 at the ends
+    next: usize,
+    free: usize, // first free object, 0 if none
+    in_use: usize,
+}
+
+/// Usage of one cache.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SlabStats {
+    pub object_size: usize,
+    pub objects_per_slab: usize,
+    pub slabs: usize,
+    pub empty_slabs: usize,
+    pub active_objects: usize,
+    pub allocations: usize, // since boot
+}
+
+struct Lists {
+    full: usize,
+    partial: usize,
+    empty: usize,
+    slabs: usize,
+    empty_slabs: usize,
+    active_objects: usize,
+    allocations: usize,
+}
+
+/// A named cache of fixed-size objects, meant to be a static:
+///
+/// `static NODES: SlabCache = SlabCache::of::<Node>("fs_node").constructor(zero_node);`
+pub struct SlabCache {
+    name: &'static str,
+    object_size: usize,
+    align: usize,
+    constructor: Option<fn(*mut u8)>,
+    lists: Mutex<Lists>,
+    registered: AtomicBool, // listed in CACHES yet
+}
+
+// all caches that were used so far, for `slabinfo`
+static CACHES: Mutex<Vec<&'static SlabCache>> = Mutex::new(Vec::new());
+
+impl SlabCache {
+    /// A cache for objects of `size` bytes aligned to `align`.
+    pub const fn new(name: &'static str, size: usize, align: usize) -> Self {
+        assert!(size <= MAX_OBJECT_SIZE && align.is_power_of_two() && align <= MAX_OBJECT_SIZE);
+        SlabCache {
+            name,
+            object_size: size,
+            align,
+            constructor: None,
+            lists: Mutex::new(Lists {
+                full: 0,
+                partial: 0,
+                empty: 0,
+                slabs: 0,
+                empty_slabs: 0,
+                active_objects: 0,
+                allocations: 0,
+            }),
+            registered: AtomicBool::new(false),
+        }
+    }
+
+    /// A cache for `T`s.
+    pub const fn of<T>(name: &'static str) -> Self {
+        Self::new(name, mem::size_of::<T>(), mem::align_of::<T>())
+    }
+
+    /// Runs `constructor` on every object of a new slab, once. Objects come back from `alloc` in
+    /// the state `free` got them in, so users that keep them constructed skip the setup.
+    pub const fn constructor(mut self, constructor: fn(*mut u8)) -> Self {
+        self.constructor = Some(constructor);
+        self
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    pub fn object_size(&self) -> usize {
+        self.object_size
+    }
+
+    // offset of the link after an object, and distance between objects
+    fn link_offset(&self) -> usize {
+        align_up(self.object_size, mem::align_of::<usize>())
+    }
+
+    fn stride(&self) -> usize {
+        align_up(self.link_offset() + mem::size_of::<usize>(), self.align)
+    }
+
+    fn first_object(&self) -> usize {
+        align_up(mem::size_of::<Slab>(), self.align)
+    }
+
+    fn objects_per_slab(&self) -> usize {
+        (SLAB_SIZE - self.first_object()) / self.stride()
+    }
+
+    pub fn stats(&self) -> SlabStats {
+        let lists = self.lists.lock();
+        SlabStats {
+            object_size: self.object_size,
+            objects_per_slab: self.objects_per_slab(),
+            slabs: lists.slabs,
+            empty_slabs: lists.empty_slabs,
+            active_objects: lists.active_objects,
+            allocations: lists.allocations,
+        }
+    }
+
+    /// Hands out an object, taking a new slab from the frame allocator if all are full.
+    /// Returns None if there are no frames left.
+    pub fn alloc(&'static self) -> Option<NonNull<u8>> {
+        if !self.registered.swap(true, Ordering::SeqCst) {
+            CACHES.lock().push(self);
+        }
+
+        let mut lists = self.lists.lock();
+        let slab = if lists.partial != 0 {
+            lists.partial
+        } else if lists.empty != 0 {
+            let slab = lists.empty;
+            unsafe {
+                unlink(&mut lists.empty, slab);
+                push(&mut lists.partial, slab);
+            }
+            lists.empty_slabs -= 1;
+            slab
+        } else {
+            let slab = self.new_slab()?;
+            unsafe { push(&mut lists.partial, slab) };
+            lists.slabs += 1;
+            slab
+        };
+
+        unsafe {
+            let header = header(slab);
+            let object = header.free;
+            header.free = *((object + self.link_offset()) as *const usize);
+            header.in_use += 1;
+            if header.free == 0 {
+                unlink(&mut lists.partial, slab);
+                push(&mut lists.full, slab);
+            }
+            lists.active_objects += 1;
+            lists.allocations += 1;
+            NonNull::new(object as *mut u8)
+        }
+    }
+
+    /// Gives an object back.
+    ///
+    /// Unsafe because `object` must come from `alloc` of this cache and not be used any more.
+    pub unsafe fn free(&self, object: NonNull<u8>) {
+        let object = object.as_ptr() as usize;
+        let slab = object & !(SLAB_SIZE - 1);
+        let mut lists = self.lists.lock();
+        let header = header(slab);
+        let was_full = header.free == 0;
+
+        *((object + self.link_offset()) as *mut usize) = header.free;
+        header.free = object;
+        header.in_use -= 1;
+        lists.active_objects -= 1;
+
+        if header.in_use == 0 {
+            if was_full {
+                unlink(&mut lists.full, slab); // a slab of one object
+            } else {
+                unlink(&mut lists.partial, slab);
+            }
+            if lists.empty_slabs == 0 {
+                push(&mut lists.empty, slab);
+                lists.empty_slabs += 1;
+            } else {
+                release_slab(slab);
+                lists.slabs -= 1;
+            }
+        } else if was_full {
+            unlink(&mut lists.full, slab);
+            push(&mut lists.partial, slab);
+        }
+    }
+
+    /// Gives the empty slabs back to the frame allocator, returns how many.
+    pub fn reclaim(&self) -> usize {
+        let mut lists = self.lists.lock();
+        let mut reclaimed = 0;
+        while lists.empty != 0 {
+            let slab = lists.empty;
+            unsafe {
+                unlink(&mut lists.empty, slab);
+                release_slab(slab);
+            }
+            reclaimed += 1;
+        }
+        lists.slabs -= reclaimed;
+        lists.empty_slabs = 0;
+        reclaimed
+    }
+
+    // takes a frame and lays out a slab in it, all objects free and constructed
+    fn new_slab(&self) -> Option<usize> {
+        let frame = FRAME_ALLOCATOR.lock().as_mut()?.allocate_frame()?;
+        let slab = memory::phys_to_virt(frame.start_address()).as_u64() as usize;
+
+        let first = slab + self.first_object();
+        let count = self.objects_per_slab();
+        for index in 0..count {
+            let object = first + index * self.stride();
+            let next = if index + 1 < count { object + self.stride() } else { 0 };
+            unsafe { *((object + self.link_offset()) as *mut usize) = next };
+            if let Some(constructor) = self.constructor {
+                constructor(object as *mut u8);
+            }
+        }
+        unsafe {
+            (slab as *mut Slab).write(Slab {
+                prev: 0,
+                next: 0,
+                free: first,
+                in_use: 0,
+            })
+        };
+        Some(slab)
+    }
+}
+
+unsafe fn header(slab: usize) -> &'static mut Slab {
+    &mut *(slab as *mut Slab)
+}
+
+unsafe fn push(list: &mut usize, slab: usize) {
+    let header = header(slab);
+    header.prev = 0;
+    header.next = *list;
+    if *list != 0 {
+        self::header(*list).prev = slab;
+    }
+    *list = slab;
+}
+
+unsafe fn unlink(list: &mut usize, slab: usize) {
+    let header = header(slab);
+    if header.prev != 0 {
+        self::header(header.prev).next = header.next;
+    } else {
+        *list = header.next;
+    }
+    if header.next != 0 {
+        self::header(header.next).prev = header.prev;
+    }
+}
+
+unsafe fn release_slab(slab: usize) {
+    let frame = PhysFrame::containing_address(memory::virt_to_phys(VirtAddr::new(slab as u64)));
+    if let Some(frames) = FRAME_ALLOCATOR.lock().as_mut() {
+        frames.deallocate_frame(frame);
+    }
+}
+
+/// All caches that have been used, in the order they were first used.
+pub fn caches() -> Vec<&'static SlabCache> {
+    CACHES.lock().clone()
+}
+
+/// Gives the empty slabs of every cache back to the frame allocator, returns how many.
+pub fn reclaim_all() -> usize {
+    caches().iter().map(|cache| cache.reclaim()).sum()
+}
+
+/// An object in a slab cache, like a `Box` but allocated from `cache`.
+pub struct SlabBox<T: 'static> {
+    object: NonNull<T>,
+    cache: &'static SlabCache,
+}
+
+impl<T> SlabBox<T> {
+    /// Moves `value` into an object from `cache`. Returns None if out of memory.
+    /// Panics if the cache's objects are too small or not aligned enough for a `T`.
+    pub fn new_in(value: T, cache: &'static SlabCache) -> Option<Self> {
+        assert!(mem::size_of::<T>() <= cache.object_size && mem::align_of::<T>() <= cache.align);
+        let object = cache.alloc()?.cast::<T>();
+        unsafe { object.as_ptr().write(value) };
+        Some(SlabBox { object, cache })
+    }
+}
+
+// owns its object like a `Box` does
+unsafe impl<T: Send> Send for SlabBox<T> {}
+unsafe impl<T: Sync> Sync for SlabBox<T> {}
+
+impl<T> Deref for SlabBox<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { self.object.as_ref() }
+    }
+}
+
+impl<T> DerefMut for SlabBox<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { self.object.as_mut() }
+    }
+}
+
+impl<T> Drop for SlabBox<T> {
+    fn drop(&mut self) {
+        unsafe {
+            ptr::drop_in_place(self.object.as_ptr());
+            self.cache.free(self.object.cast());
+        }
+    }
+}
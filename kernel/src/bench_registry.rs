@@ -0,0 +1,232 @@
+use alloc::{collections::BTreeMap, vec::Vec};
+use core::arch::x86_64::_rdtsc;
+use core::sync::atomic::{AtomicU64, Ordering};
+use spin::Mutex;
+use lazy_static::lazy_static;
+
+use crate::{println, println_colored, error, recovery, time};
+use crate::vga_buffer::Color;
+
+// Benchmarks: like tests, but a benchmark is run many times and every run is timed
+// with the time stamp counter. The TSC rate is measured against the PIT once.
+
+pub type BenchFunction = fn() -> ();
+
+/// Runs per benchmark unless it asks for something else.
+pub const DEFAULT_ITERATIONS: usize = 1000;
+const WARMUP_ITERATIONS: usize = 10;
+const CALIBRATION_TICKS: u64 = 10;
+
+#[derive(Debug, Clone, Copy)]
+pub struct RegisteredBench {
+    pub function: BenchFunction,
+    pub iterations: usize,
+}
+
+lazy_static! {
+    pub static ref BENCH_REGISTRY: Mutex<BTreeMap<&'static str, RegisteredBench>> = Mutex::new(BTreeMap::new());
+}
+
+/// A benchmark placed in the `kernel_benches` linker section by `kernel_bench!`.
+pub struct BenchEntry {
+    pub name: &'static str,
+    pub bench: RegisteredBench,
+}
+
+/// Registers a benchmark at boot: `kernel_bench!(box_churn);` or `kernel_bench!("vec", vec_growth, 100);`
+#[macro_export]
+macro_rules! kernel_bench {
+    ($function:ident $(, $iterations:expr)?) => {
+        $crate::kernel_bench!(stringify!($function), $function $(, $iterations)?);
+    };
+    ($name:expr, $function:path) => {
+        $crate::kernel_bench!($name, $function, $crate::bench_registry::DEFAULT_ITERATIONS);
+    };
+    ($name:expr, $function:path, $iterations:expr) => {
+        const _: () = {
+            #[used]
+            #[link_section = "kernel_benches"]
+            static BENCH: $crate::bench_registry::BenchEntry = $crate::bench_registry::BenchEntry {
+                name: $name,
+                bench: $crate::bench_registry::RegisteredBench { function: $function, iterations: $iterations },
+            };
+        };
+    };
+}
+
+// the linker defines these around the `kernel_benches` section, weak so an empty section still links
+extern "C" {
+    #[linkage = "extern_weak"]
+    static __start_kernel_benches: *const u8;
+    #[linkage = "extern_weak"]
+    static __stop_kernel_benches: *const u8;
+}
+
+/// Registers every benchmark declared with `kernel_bench!`. Needs the heap.
+pub fn register_linked_benches() {
+    let entries = unsafe { crate::linker_section::<BenchEntry>(__start_kernel_benches, __stop_kernel_benches) };
+    let mut registry = BENCH_REGISTRY.lock();
+    for entry in entries {
+        registry.insert(entry.name, entry.bench);
+    }
+}
+
+pub fn register_bench(name: &'static str, function: BenchFunction, iterations: usize) {
+    BENCH_REGISTRY.lock().insert(name, RegisteredBench { function, iterations });
+}
+
+/// Names of all registered benchmarks, sorted.
+pub fn bench_names() -> Vec<&'static str> {
+    BENCH_REGISTRY.lock().keys().copied().collect()
+}
+
+// TSC cycles per second, 0 until measured
+static TSC_HZ: AtomicU64 = AtomicU64::new(0);
+
+/// The time stamp counter rate in Hz, measured against the PIT the first time it's needed.
+/// Needs interrupts enabled, the measurement waits for `CALIBRATION_TICKS` timer ticks.
+pub fn tsc_hz() -> u64 {
+    let known = TSC_HZ.load(Ordering::Relaxed);
+    if known != 0 {
+        return known;
+    }
+
+    // start right at a tick edge, so the window is whole ticks
+    let edge = time::ticks();
+    while time::ticks() == edge {
+        core::hint::spin_loop();
+    }
+    let start_tick = time::ticks();
+    let start = unsafe { _rdtsc() };
+    while time::ticks() < start_tick + CALIBRATION_TICKS {
+        core::hint::spin_loop();
+    }
+    let cycles = unsafe { _rdtsc() } - start;
+
+    let hz = cycles * time::TIMER_HZ / CALIBRATION_TICKS;
+    TSC_HZ.store(hz, Ordering::Relaxed);
+    hz
+}
+
+/// Summary of the per-iteration cycle counts of one benchmark run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Statistics {
+    pub iterations: usize,
+    pub min: u64,
+    pub mean: u64,
+    pub median: u64,
+    pub stddev: u64,
+}
+
+impl Statistics {
+    /// Computes the statistics of `samples`. Sorts them. Returns None for no samples.
+    pub fn from_samples(samples: &mut [u64]) -> Option<Statistics> {
+        if samples.is_empty() {
+            return None;
+        }
+        samples.sort_unstable();
+
+        let count = samples.len() as u64;
+        let mean = samples.iter().sum::<u64>() / count;
+        let median = match samples.len() % 2 {
+            0 => (samples[samples.len() / 2 - 1] + samples[samples.len() / 2]) / 2,
+            _ => samples[samples.len() / 2],
+        };
+        // squares of differences in the millions of cycles don't fit in a u64
+        let variance = samples
+            .iter()
+            .map(|&sample| {
+                let difference = sample.abs_diff(mean) as u128;
+                difference * difference
+            })
+            .sum::<u128>()
+            / count as u128;
+
+        Some(Statistics {
+            iterations: samples.len(),
+            min: samples[0],
+            mean,
+            median,
+            stddev: integer_sqrt(variance),
+        })
+    }
+}
+
+// floor of the square root, no floats in the kernel
+fn integer_sqrt(value: u128) -> u64 {
+    if value < 2 {
+        return value as u64;
+    }
+    // Newton's method, starting above the root
+    let mut x = value;
+    let mut y = x.div_ceil(2);
+    while y < x {
+        x = y;
+        y = (x + value / x) / 2;
+    }
+    x as u64
+}
+
+/// Times `function` over `iterations` runs (after a few warmup runs).
+pub fn measure(function: BenchFunction, iterations: usize) -> Option<Statistics> {
+    for _ in 0..WARMUP_ITERATIONS {
+        function();
+    }
+
+    let mut samples = Vec::with_capacity(iterations);
+    for _ in 0..iterations {
+        let start = unsafe { _rdtsc() };
+        function();
+        let end = unsafe { _rdtsc() };
+        samples.push(end.saturating_sub(start));
+    }
+    Statistics::from_samples(&mut samples)
+}
+
+/// Runs the named benchmark and prints its statistics.
+pub fn run_bench(name: &str) {
+    let bench = BENCH_REGISTRY.lock().get(name).copied();
+    match bench {
+        Some(bench) => run(name, bench),
+        None => error!("Benchmark {} not found", name),
+    }
+}
+
+/// Runs every benchmark.
+pub fn run_all() {
+    let benches: Vec<(&'static str, RegisteredBench)> =
+        BENCH_REGISTRY.lock().iter().map(|(&name, &bench)| (name, bench)).collect();
+    for (name, bench) in benches {
+        run(name, bench);
+    }
+}
+
+fn run(name: &str, bench: RegisteredBench) {
+    let hz = tsc_hz();
+    match recovery::catch_panic(|| measure(bench.function, bench.iterations)) {
+        Ok(Some(stats)) => {
+            println!(
+                "{}: {} runs, min {} mean {} median {} stddev {} cycles, mean {} ns",
+                name,
+                stats.iterations,
+                stats.min,
+                stats.mean,
+                stats.median,
+                stats.stddev,
+                cycles_to_nanoseconds(stats.mean, hz)
+            );
+        }
+        Ok(None) => println!("{}: no runs", name),
+        Err(message) => {
+            println_colored!(Color::LightRed, "{} [failed]", name);
+            error!("  {}", message);
+        }
+    }
+}
+
+fn cycles_to_nanoseconds(cycles: u64, hz: u64) -> u64 {
+    if hz == 0 {
+        return 0;
+    }
+    (cycles as u128 * 1_000_000_000 / hz as u128) as u64
+}
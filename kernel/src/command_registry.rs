@@ -1,7 +1,7 @@
-use alloc::collections::BTreeMap;
+use alloc::{collections::BTreeMap, vec::Vec};
 use spin::Mutex;
 use lazy_static::lazy_static;
-use crate::println;
+use crate::error;
 
 // type alias for command with arguments
 pub type CommandWithArgs = fn(&str);
@@ -10,9 +10,11 @@ pub type CommandWithArgs = fn(&str);
 pub type Command = fn();
 
 // enum with command variants
+#[derive(Clone, Copy)]
 pub enum CommandFunction {
     WithArgs(CommandWithArgs),
     NoArgs(Command),
+    OptionalArgs(CommandWithArgs), // gets "" when run without arguments
 }
 
 // global registry for commands
@@ -20,41 +22,127 @@ lazy_static! {
     pub static ref COMMAND_REGISTRY: Mutex<BTreeMap<&'static str, CommandFunction>> = Mutex::new(BTreeMap::new());
 }
 
+/// A command placed in the `kernel_commands` linker section by `kernel_command!`.
+pub struct CommandEntry {
+    pub name: &'static str,
+    pub function: CommandFunction,
+}
+
+/// Registers a command without arguments at boot: `kernel_command!("bsod", execute);`
+#[macro_export]
+macro_rules! kernel_command {
+    ($name:literal, $function:path) => {
+        const _: () = {
+            #[used]
+            #[link_section = "kernel_commands"]
+            static COMMAND: $crate::command_registry::CommandEntry = $crate::command_registry::CommandEntry {
+                name: $name,
+                function: $crate::command_registry::CommandFunction::NoArgs($function),
+            };
+        };
+    };
+}
+
+/// Registers a command taking arguments at boot: `kernel_command_with_args!("echo", execute);`
+#[macro_export]
+macro_rules! kernel_command_with_args {
+    ($name:literal, $function:path) => {
+        const _: () = {
+            #[used]
+            #[link_section = "kernel_commands"]
+            static COMMAND: $crate::command_registry::CommandEntry = $crate::command_registry::CommandEntry {
+                name: $name,
+                function: $crate::command_registry::CommandFunction::WithArgs($function),
+            };
+        };
+    };
+}
+
+/// Registers a command that also runs without arguments at boot: `kernel_command_with_optional_args!("dmesg", execute);`
+#[macro_export]
+macro_rules! kernel_command_with_optional_args {
+    ($name:literal, $function:path) => {
+        const _: () = {
+            #[used]
+            #[link_section = "kernel_commands"]
+            static COMMAND: $crate::command_registry::CommandEntry = $crate::command_registry::CommandEntry {
+                name: $name,
+                function: $crate::command_registry::CommandFunction::OptionalArgs($function),
+            };
+        };
+    };
+}
+
+// the linker defines these around the `kernel_commands` section, weak so an empty section still links
+extern "C" {
+    #[linkage = "extern_weak"]
+    static __start_kernel_commands: *const u8;
+    #[linkage = "extern_weak"]
+    static __stop_kernel_commands: *const u8;
+}
+
+/// Registers every command declared with one of the `kernel_command*!` macros.
+/// Needs the heap.
+pub fn register_linked_commands() {
+    let entries = unsafe { crate::linker_section::<CommandEntry>(__start_kernel_commands, __stop_kernel_commands) };
+    let mut registry = COMMAND_REGISTRY.lock();
+    for entry in entries {
+        registry.insert(entry.name, entry.function);
+    }
+}
+
 // A function to register a command with arguments
 pub fn register_command_with_args(name: &'static str, command: CommandWithArgs) {
     COMMAND_REGISTRY.lock().insert(name, CommandFunction::WithArgs(command));
 }
 
+// A function to register a command that also runs without arguments, with an empty argument string
+pub fn register_command_with_optional_args(name: &'static str, command: CommandWithArgs) {
+    COMMAND_REGISTRY.lock().insert(name, CommandFunction::OptionalArgs(command));
+}
+
 // A function to register a command without arguments
 pub fn register_command(name: &'static str, command: Command) {
     COMMAND_REGISTRY.lock().insert(name, CommandFunction::NoArgs(command));
 }
 
+// Names of all registered commands, sorted
+pub fn command_names() -> Vec<&'static str> {
+    COMMAND_REGISTRY.lock().keys().copied().collect()
+}
+
+// Returns true if the command can only run with arguments
+pub fn takes_arguments(name: &str) -> bool {
+    matches!(COMMAND_REGISTRY.lock().get(name), Some(CommandFunction::WithArgs(_)))
+}
+
 pub fn run_command(command_line: &str) {
     let trimmed_command_line = command_line.trim();
     let mut parts = trimmed_command_line.splitn(2, ' ');
     let command_name = parts.next().unwrap_or("");
     let args = parts.next();
 
-    let command_registry = COMMAND_REGISTRY.lock();
-    if let Some(command_function) = command_registry.get(command_name) {
+    // copy the function out, so the command itself can use the registry
+    let command_function = COMMAND_REGISTRY.lock().get(command_name).copied();
+    if let Some(command_function) = command_function {
         match command_function {
             CommandFunction::WithArgs(function) => {
                 if let Some(arguments) = args {
                     function(arguments);
                 } else {
-                    println!("Error: {} requires arguments", command_name);
+                    error!("Error: {} requires arguments", command_name);
                 }
             },
+            CommandFunction::OptionalArgs(function) => function(args.unwrap_or("")),
             CommandFunction::NoArgs(function) => {
                 if args.is_none() {
                     function();
                 } else {
-                    println!("Error: {} does not accept arguments", command_name);
+                    error!("Error: {} does not accept arguments", command_name);
                 }
             },
         }
     } else {
-        println!("{} not found", command_name);
+        error!("{} not found", command_name);
     }
 }
\ No newline at end of file
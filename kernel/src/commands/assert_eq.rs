@@ -1,6 +1,8 @@
 use crate::println;
 use alloc::vec::Vec;
 
+crate::kernel_command_with_args!("assert_eq", execute);
+
 pub fn execute(args: &str) {
     let values: Vec<&str> = args.split_whitespace().collect();
     let length = values.len();
@@ -0,0 +1,19 @@
+use crate::{println, bench_registry};
+
+crate::kernel_command_with_optional_args!("bench", execute);
+
+// `bench` lists the benchmarks, `bench all` runs all of them and `bench <name>` a single one.
+pub fn execute(args: &str) {
+    match args.trim() {
+        "" => list_benches(),
+        "all" => bench_registry::run_all(),
+        name => bench_registry::run_bench(name),
+    }
+}
+
+fn list_benches() {
+    println!("Usage: bench all | bench <name>");
+    for name in bench_registry::bench_names() {
+        println!("  {}", name);
+    }
+}
@@ -1,15 +1,19 @@
-use crate::vga_buffer::{self, WRITER};
+use crate::{console, vga_buffer};
 use core::panic::PanicInfo;
 use core::sync::atomic::{AtomicBool, Ordering};
 
 pub static BSOD_ACTIVE: AtomicBool = AtomicBool::new(false);
 
+crate::kernel_command!("bsod", execute);
+
 pub fn execute() {
     panic!("PSOD (Puk Screen Of Death)");
 }
 
 pub fn handle_bsod(info: &PanicInfo) {
-    let mut writer = WRITER.lock();  
+    crate::serial_println!("KERNEL PANIC: {}", info); // the screen isn't visible on headless runs
+
+    let mut writer = console::active_writer().lock();  
 
     writer.set_screen_color(vga_buffer::Color::Blue);
 
@@ -0,0 +1,16 @@
+use crate::{error, fs, print, println};
+
+crate::kernel_command_with_args!("cat", execute);
+
+pub fn execute(args: &str) {
+    let name = args.trim();
+    match fs::read(name) {
+        Ok(data) => {
+            print!("{}", alloc::string::String::from_utf8_lossy(&data));
+            if !data.ends_with(b"\n") {
+                println!();
+            }
+        }
+        Err(_) => error!("No such file: {}", name),
+    }
+}
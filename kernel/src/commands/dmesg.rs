@@ -0,0 +1,31 @@
+use crate::{error, println, println_colored};
+use crate::log::{self, Level, Timestamp};
+use crate::vga_buffer::Color;
+
+crate::kernel_command_with_optional_args!("dmesg", execute);
+
+// `dmesg` shows the whole kernel log, `dmesg warn` only warnings and errors, `dmesg clear` empties it.
+pub fn execute(args: &str) {
+    let level = match args.trim() {
+        "" => Level::Trace,
+        "clear" => {
+            log::clear();
+            return;
+        }
+        name => match Level::from_name(name) {
+            Some(level) => level,
+            None => {
+                error!("Usage: dmesg [error|warn|info|debug|trace|clear]");
+                return;
+            }
+        },
+    };
+
+    for record in log::records(level) {
+        match record.level {
+            Level::Error => println_colored!(Color::LightRed, "[{}] {}", Timestamp(record.ticks), record.message()),
+            Level::Warn => println_colored!(Color::Yellow, "[{}] {}", Timestamp(record.ticks), record.message()),
+            _ => println!("[{}] {}", Timestamp(record.ticks), record.message()),
+        }
+    }
+}
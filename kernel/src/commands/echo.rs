@@ -1,5 +1,7 @@
 use crate::println;
 
+crate::kernel_command_with_args!("echo", execute);
+
 pub fn execute(args: &str) {
     println!("{}", args);
 }
\ No newline at end of file
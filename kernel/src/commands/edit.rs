@@ -0,0 +1,407 @@
+use crate::{console, error, fs};
+use crate::task::keyboard;
+use crate::vga_buffer::{cp437, Color, ColorCode, Snapshot, STATUS_ROWS};
+use alloc::{boxed::Box, format, string::String, vec::Vec};
+use pc_keyboard::{DecodedKey, KeyCode};
+use x86_64::instructions::interrupts;
+
+// A nano-style editor for files in the ramfs. The screen below the status bar is split into
+// a title bar, the text, a message line and a help line.
+
+const TEXT_COLOR: ColorCode = ColorCode::new(Color::LightGray, Color::Black);
+const BAR_COLOR: ColorCode = ColorCode::new(Color::Black, Color::LightGray);
+const TAB_WIDTH: usize = 4;
+const CHROME_ROWS: usize = 3; // title, message and help line
+
+// what pc-keyboard sends for Ctrl+letter
+const CTRL_S: char = '\x13';
+const CTRL_W: char = '\x17';
+const CTRL_X: char = '\x18';
+const ESCAPE: char = '\x1b';
+const DELETE: char = '\x7f';
+
+const HELP: &str = "^S Save   ^W Search   ^X Exit";
+
+crate::kernel_command_with_args!("edit", execute);
+
+pub fn execute(args: &str) {
+    let name = args.trim();
+    if !fs::is_valid_name(name) {
+        error!("Usage: edit <file>");
+        return;
+    }
+
+    let text = match fs::read(name) {
+        Ok(data) => String::from_utf8_lossy(&data).into_owned(),
+        Err(_) => String::new(), // new file, created on the first save
+    };
+
+    let mut editor = Editor::new(name, &text);
+    editor.open();
+    keyboard::grab(Box::new(move |key| {
+        let keep_running = editor.handle_key(key);
+        if keep_running {
+            editor.draw();
+        } else {
+            editor.close();
+        }
+        keep_running
+    }));
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Edit,
+    Search,
+    ConfirmExit,
+}
+
+pub struct Editor {
+    name: String,
+    lines: Vec<Vec<char>>,
+    row: usize,    // cursor position in the text
+    column: usize,
+    top: usize,    // first visible line
+    left: usize,   // first visible column
+    modified: bool,
+    mode: Mode,
+    query: String,
+    last_query: String,
+    message: String,
+    console: usize,
+    saved: Option<Snapshot>, // the console content while the editor is open
+}
+
+impl Editor {
+    /// Creates an editor for `name` showing `text` on the active console.
+    pub fn new(name: &str, text: &str) -> Editor {
+        // tabs stay tabs, so saving writes them back; they are only expanded on screen
+        let lines = text.split('\n').map(|line| line.chars().filter(|&character| character != '\r').collect()).collect();
+
+        Editor {
+            name: String::from(name),
+            lines,
+            row: 0,
+            column: 0,
+            top: 0,
+            left: 0,
+            modified: false,
+            mode: Mode::Edit,
+            query: String::new(),
+            last_query: String::new(),
+            message: String::new(),
+            console: console::active(),
+            saved: None,
+        }
+    }
+
+    /// The edited text, lines joined with '\n'.
+    pub fn text(&self) -> String {
+        let mut text = String::new();
+        for (index, line) in self.lines.iter().enumerate() {
+            if index > 0 {
+                text.push('\n');
+            }
+            text.extend(line.iter());
+        }
+        text
+    }
+
+    pub fn cursor(&self) -> (usize, usize) {
+        (self.row, self.column)
+    }
+
+    pub fn is_modified(&self) -> bool {
+        self.modified
+    }
+
+    /// Handles one key. Returns false once the editor wants to exit.
+    pub fn handle_key(&mut self, key: DecodedKey) -> bool {
+        let keep_running = match self.mode {
+            Mode::Edit => self.edit_key(key),
+            Mode::Search => {
+                self.search_key(key);
+                true
+            }
+            Mode::ConfirmExit => self.confirm_exit_key(key),
+        };
+        self.scroll_to_cursor();
+        keep_running
+    }
+
+    fn edit_key(&mut self, key: DecodedKey) -> bool {
+        self.message.clear();
+        match key {
+            DecodedKey::Unicode(CTRL_S) => self.save(),
+            DecodedKey::Unicode(CTRL_X) => {
+                if !self.modified {
+                    return false;
+                }
+                self.mode = Mode::ConfirmExit;
+            }
+            DecodedKey::Unicode(CTRL_W) => {
+                self.mode = Mode::Search;
+                self.query.clear();
+            }
+            DecodedKey::Unicode('\n') => self.insert_newline(),
+            DecodedKey::Unicode('\x08') => self.backspace(),
+            DecodedKey::Unicode(DELETE) | DecodedKey::RawKey(KeyCode::Delete) => self.delete(),
+            DecodedKey::Unicode('\t') => self.insert_char('\t'),
+            DecodedKey::Unicode(character) if !character.is_control() => self.insert_char(character),
+            DecodedKey::RawKey(code) => self.move_cursor(code),
+            DecodedKey::Unicode(_) => {}
+        }
+        true
+    }
+
+    fn search_key(&mut self, key: DecodedKey) {
+        match key {
+            DecodedKey::Unicode('\n') => {
+                if !self.query.is_empty() {
+                    self.last_query = self.query.clone();
+                }
+                self.mode = Mode::Edit;
+                self.find_next();
+            }
+            DecodedKey::Unicode('\x08') => {
+                self.query.pop();
+            }
+            DecodedKey::Unicode(ESCAPE) | DecodedKey::Unicode(CTRL_W) => {
+                self.mode = Mode::Edit;
+                self.message.clear();
+            }
+            DecodedKey::Unicode(character) if !character.is_control() => self.query.push(character),
+            _ => {}
+        }
+    }
+
+    fn confirm_exit_key(&mut self, key: DecodedKey) -> bool {
+        match key {
+            DecodedKey::Unicode('y') | DecodedKey::Unicode('Y') => {
+                self.save();
+                if self.modified {
+                    self.mode = Mode::Edit; // saving failed, keep the changes on screen
+                    return true;
+                }
+                false
+            }
+            DecodedKey::Unicode('n') | DecodedKey::Unicode('N') => false,
+            DecodedKey::Unicode(ESCAPE) => {
+                self.mode = Mode::Edit;
+                true
+            }
+            _ => true,
+        }
+    }
+
+    fn save(&mut self) {
+        match fs::write(&self.name, self.text().as_bytes()) {
+            Ok(()) => {
+                self.modified = false;
+                self.message = format!("Wrote {} lines", self.lines.len());
+            }
+            Err(err) => self.message = format!("Can't save {}: {:?}", self.name, err),
+        }
+    }
+
+    /// Searches forward from the cursor for the last query, wrapping around at the end.
+    fn find_next(&mut self) {
+        let query: Vec<char> = self.last_query.chars().collect();
+        if query.is_empty() {
+            return;
+        }
+
+        let count = self.lines.len();
+        for step in 0..=count {
+            let row = (self.row + step) % count;
+            // on the cursor line, start right after the cursor (or from the start after wrapping)
+            let start = if step == 0 { self.column + 1 } else { 0 };
+            let line = &self.lines[row];
+            if let Some(offset) = find(&line[start.min(line.len())..], &query) {
+                self.row = row;
+                self.column = start + offset;
+                return;
+            }
+        }
+        self.message = format!("\"{}\" not found", self.last_query);
+    }
+
+    fn insert_char(&mut self, character: char) {
+        self.lines[self.row].insert(self.column, character);
+        self.column += 1;
+        self.modified = true;
+    }
+
+    fn insert_newline(&mut self) {
+        let rest = self.lines[self.row].split_off(self.column);
+        self.lines.insert(self.row + 1, rest);
+        self.row += 1;
+        self.column = 0;
+        self.modified = true;
+    }
+
+    fn backspace(&mut self) {
+        if self.column > 0 {
+            self.column -= 1;
+            self.lines[self.row].remove(self.column);
+        } else if self.row > 0 {
+            // join with the previous line
+            let line = self.lines.remove(self.row);
+            self.row -= 1;
+            self.column = self.lines[self.row].len();
+            self.lines[self.row].extend(line);
+        } else {
+            return;
+        }
+        self.modified = true;
+    }
+
+    fn delete(&mut self) {
+        if self.column < self.lines[self.row].len() {
+            self.lines[self.row].remove(self.column);
+        } else if self.row + 1 < self.lines.len() {
+            let next = self.lines.remove(self.row + 1);
+            self.lines[self.row].extend(next);
+        } else {
+            return;
+        }
+        self.modified = true;
+    }
+
+    fn move_cursor(&mut self, code: KeyCode) {
+        let page = self.text_rows();
+        let last_row = self.lines.len() - 1;
+        match code {
+            KeyCode::ArrowLeft if self.column > 0 => self.column -= 1,
+            KeyCode::ArrowLeft if self.row > 0 => {
+                self.row -= 1;
+                self.column = self.lines[self.row].len();
+            }
+            KeyCode::ArrowRight if self.column < self.lines[self.row].len() => self.column += 1,
+            KeyCode::ArrowRight if self.row < last_row => {
+                self.row += 1;
+                self.column = 0;
+            }
+            KeyCode::ArrowUp => self.row = self.row.saturating_sub(1),
+            KeyCode::ArrowDown => self.row = (self.row + 1).min(last_row),
+            KeyCode::PageUp => self.row = self.row.saturating_sub(page),
+            KeyCode::PageDown => self.row = (self.row + page).min(last_row),
+            KeyCode::Home => self.column = 0,
+            KeyCode::End => self.column = self.lines[self.row].len(),
+            _ => {}
+        }
+        self.column = self.column.min(self.lines[self.row].len());
+    }
+
+    fn screen_size(&self) -> (usize, usize) {
+        let writer = console::writer(self.console).lock();
+        (writer.width(), writer.height() - STATUS_ROWS)
+    }
+
+    fn text_rows(&self) -> usize {
+        let (_, height) = self.screen_size();
+        height.saturating_sub(CHROME_ROWS).max(1)
+    }
+
+    fn scroll_to_cursor(&mut self) {
+        let (width, _) = self.screen_size();
+        let rows = self.text_rows();
+        if self.row < self.top {
+            self.top = self.row;
+        } else if self.row >= self.top + rows {
+            self.top = self.row + 1 - rows;
+        }
+        let column = display_column(&self.lines[self.row], self.column);
+        if column < self.left {
+            self.left = column;
+        } else if column >= self.left + width {
+            self.left = column + 1 - width;
+        }
+    }
+
+    /// Saves the console content and draws the editor over it.
+    fn open(&mut self) {
+        interrupts::without_interrupts(|| {
+            self.saved = Some(console::writer(self.console).lock().snapshot());
+        });
+        self.draw();
+    }
+
+    /// Puts the console back the way it was before the editor opened.
+    fn close(&mut self) {
+        if let Some(snapshot) = self.saved.take() {
+            interrupts::without_interrupts(|| {
+                console::writer(self.console).lock().restore(&snapshot);
+            });
+        }
+    }
+
+    fn draw(&self) {
+        let (width, height) = self.screen_size();
+        let rows = self.text_rows();
+
+        interrupts::without_interrupts(|| {
+            let mut writer = console::writer(self.console).lock();
+            let mut put_line = |row: usize, text: &mut dyn Iterator<Item = char>, color: ColorCode| {
+                let mut text = text.map(cp437::to_glyph);
+                for column in 0..width {
+                    let glyph = text.next().unwrap_or(b' ');
+                    writer.put_glyph_at(glyph, color, column, row + STATUS_ROWS);
+                }
+            };
+
+            let modified = if self.modified { "  [Modified]" } else { "" };
+            let title = format!("  edit: {}{}", self.name, modified);
+            put_line(0, &mut title.chars(), BAR_COLOR);
+
+            for screen_row in 0..rows {
+                let line = self.lines.get(self.top + screen_row).map_or(Vec::new(), |line| expand_tabs(line));
+                let visible = line.get(self.left..).unwrap_or(&[]);
+                put_line(screen_row + 1, &mut visible.iter().copied(), TEXT_COLOR);
+            }
+
+            let prompt = match self.mode {
+                Mode::Edit => self.message.clone(),
+                Mode::Search => format!("Search: {}", self.query),
+                Mode::ConfirmExit => String::from("Save changes? (y/n, Esc cancels)"),
+            };
+            put_line(rows + 1, &mut prompt.chars(), TEXT_COLOR);
+            put_line((rows + 2).min(height - 1), &mut HELP.chars(), BAR_COLOR);
+
+            let (cursor_column, cursor_row) = match self.mode {
+                Mode::Edit => (display_column(&self.lines[self.row], self.column) - self.left, self.row - self.top + 1),
+                Mode::Search | Mode::ConfirmExit => (prompt.chars().count().min(width - 1), rows + 1),
+            };
+            writer.place_cursor(cursor_column, cursor_row + STATUS_ROWS);
+        });
+    }
+}
+
+// screen column of `column` in `line`, tabs go to the next tab stop
+fn display_column(line: &[char], column: usize) -> usize {
+    line[..column]
+        .iter()
+        .fold(0, |x, &character| if character == '\t' { x + TAB_WIDTH - x % TAB_WIDTH } else { x + 1 })
+}
+
+// the line as it's shown, tabs replaced by spaces up to the next tab stop
+fn expand_tabs(line: &[char]) -> Vec<char> {
+    let mut expanded = Vec::with_capacity(line.len());
+    for &character in line {
+        if character == '\t' {
+            let width = TAB_WIDTH - expanded.len() % TAB_WIDTH;
+            expanded.extend_from_slice(&[' '; TAB_WIDTH][..width]);
+        } else {
+            expanded.push(character);
+        }
+    }
+    expanded
+}
+
+// index of the first occurrence of `needle` in `haystack`
+fn find(haystack: &[char], needle: &[char]) -> Option<usize> {
+    if needle.len() > haystack.len() {
+        return None;
+    }
+    haystack.windows(needle.len()).position(|window| window == needle)
+}
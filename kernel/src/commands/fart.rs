@@ -1,5 +1,7 @@
 use crate::println;
 
+crate::kernel_command!("fart", execute);
+
 pub fn execute() {
     print_fart();
 }
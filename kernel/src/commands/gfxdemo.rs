@@ -0,0 +1,58 @@
+use crate::graphics::{self, Framebuffer, GraphicsWriter, HEIGHT, WIDTH};
+use crate::task::keyboard;
+use crate::vga_buffer::Color;
+use alloc::boxed::Box;
+use core::fmt::Write;
+
+// 8x8 smiley, 0 is transparent
+const SPRITE: [u8; 64] = [
+    0, 0, 14, 14, 14, 14, 0, 0,
+    0, 14, 14, 14, 14, 14, 14, 0,
+    14, 14, 0, 14, 14, 0, 14, 14,
+    14, 14, 14, 14, 14, 14, 14, 14,
+    14, 0, 14, 14, 14, 14, 0, 14,
+    14, 14, 0, 0, 0, 0, 14, 14,
+    0, 14, 14, 14, 14, 14, 14, 0,
+    0, 0, 14, 14, 14, 14, 0, 0,
+];
+
+crate::kernel_command!("gfxdemo", execute);
+
+pub fn execute() {
+    graphics::enter();
+    draw_demo();
+
+    // any key goes back to the shell
+    keyboard::grab(Box::new(|_key| {
+        graphics::leave();
+        false
+    }));
+}
+
+fn draw_demo() {
+    let mut framebuffer = Framebuffer::new();
+
+    // color cube gradient in the background
+    for y in 0..HEIGHT {
+        for x in 0..WIDTH {
+            let color = graphics::rgb((x * 255 / WIDTH) as u8, (y * 255 / HEIGHT) as u8, 128);
+            framebuffer.put_pixel(x, y, color);
+        }
+    }
+
+    // a fan of lines from the bottom left corner
+    for step in 0..16 {
+        let x = (step * (WIDTH - 1) / 15) as isize;
+        framebuffer.line(0, HEIGHT as isize - 1, x, 40, step as u8);
+    }
+
+    framebuffer.fill_rect(200, 60, 100, 60, Color::Blue as u8);
+    framebuffer.rect(198, 58, 104, 64, Color::White as u8);
+    for index in 0..6 {
+        framebuffer.blit(210 + index * 14, 80, 8, &SPRITE, Some(0));
+    }
+
+    let mut text = GraphicsWriter::new(Color::White as u8, Color::Black as u8);
+    let _ = writeln!(text, "VertexDOS gfxdemo, mode 13h 320x200");
+    let _ = writeln!(text, "press any key to return");
+}
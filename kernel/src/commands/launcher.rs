@@ -0,0 +1,47 @@
+use crate::command_registry;
+use crate::tui::{self, theme, widgets::{Label, ListView, Menu, Window}, Event, Rect, Ui};
+use alloc::{string::String, vec::Vec};
+
+// Full screen command picker: the list shows the registered commands that take no arguments,
+// Enter (or Run in the menu) closes the launcher and runs the selected one.
+
+const MENU_ITEMS: [&str; 2] = ["Run", "Quit"];
+
+crate::kernel_command!("launcher", execute);
+
+pub fn execute() {
+    // there is no way to type arguments here, so commands taking them are left out
+    let names: Vec<&'static str> = command_registry::command_names()
+        .into_iter()
+        .filter(|&name| !command_registry::takes_arguments(name))
+        .collect();
+    let items: Vec<String> = names.iter().map(|&name| String::from(name)).collect();
+
+    let mut ui = Ui::new();
+    let (width, height) = ui.size();
+    let window = Rect::new(width / 4, 1, width / 2, height - 2);
+    let inner = window.inner();
+
+    ui.add(Window::new(window, "Launcher"));
+    let menu = ui.add(Menu::new(inner.x, inner.y, &MENU_ITEMS));
+    let list = ui.add(ListView::new(Rect::new(inner.x, inner.y + 2, inner.width, inner.height - 4), items));
+    ui.add(Label::new(inner.x, inner.bottom() - 1, inner.width, "Tab: focus  Enter: run  Esc: quit", theme::WINDOW));
+    ui.set_focus(list);
+
+    tui::run(ui, move |ui, event| {
+        let command = match event {
+            Event::Activated { widget, item } if widget == list => Some(item),
+            Event::Activated { widget, item } if widget == menu && MENU_ITEMS[item] == "Run" => {
+                ui.widget_mut::<ListView>(list).and_then(|list| list.selected())
+            }
+            _ => None,
+        };
+
+        // the console has to be back before the command prints to it
+        ui.close();
+        if let Some(&name) = command.and_then(|index| names.get(index)) {
+            command_registry::run_command(name);
+        }
+        false
+    });
+}
@@ -0,0 +1,52 @@
+use crate::{allocator, error, print, println};
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicU64, Ordering};
+
+crate::kernel_command_with_optional_args!("leaks", execute);
+
+const MAX_LISTED: usize = 20; // the newest ones, more don't fit on the screen anyway (two lines each)
+
+// allocations up to this one are left out of the list
+static MARK: AtomicU64 = AtomicU64::new(0);
+
+// `leaks` lists the heap allocations made since the last `leaks mark` that are still live,
+// with the return addresses of the code that made them. Needs the `debug-alloc` feature.
+pub fn execute(args: &str) {
+    let live = match allocator::live_allocations() {
+        Some(live) => live,
+        None => {
+            error!("leaks: allocations aren't tracked, build with --features debug-alloc");
+            return;
+        }
+    };
+
+    match args.trim() {
+        "" => {}
+        "mark" => {
+            let newest = live.iter().map(|allocation| allocation.sequence).max().unwrap_or(0);
+            MARK.store(newest, Ordering::Relaxed);
+            println!("leaks: marked, {} live allocations are left out from now on", live.len());
+            return;
+        }
+        _ => {
+            error!("Usage: leaks [mark]");
+            return;
+        }
+    }
+
+    let mark = MARK.load(Ordering::Relaxed);
+    let leaks: Vec<_> = live.iter().filter(|allocation| allocation.sequence > mark).collect();
+    let bytes: usize = leaks.iter().map(|allocation| allocation.size).sum();
+    println!("{} live allocations since the mark, {} bytes ({} in total)", leaks.len(), bytes, live.len());
+    if leaks.len() > MAX_LISTED {
+        println!("  ... {} older ones not listed", leaks.len() - MAX_LISTED);
+    }
+    for allocation in leaks.iter().skip(leaks.len().saturating_sub(MAX_LISTED)) {
+        println!("  #{:<6} {:#x} {:>6} bytes", allocation.sequence, allocation.address, allocation.size);
+        print!("    from");
+        for caller in allocation.callers.iter().take_while(|&&caller| caller != 0) {
+            print!(" {:#x}", caller);
+        }
+        println!();
+    }
+}
@@ -0,0 +1,18 @@
+use crate::{error, println};
+use crate::log::{self, Level};
+
+crate::kernel_command_with_optional_args!("loglevel", execute);
+
+// `loglevel` prints the current level, `loglevel debug` records everything up to debug messages.
+pub fn execute(args: &str) {
+    match args.trim() {
+        "" => println!("Log level: {}", log::max_level().name()),
+        name => match Level::from_name(name) {
+            Some(level) => {
+                log::set_max_level(level);
+                println!("Log level set to {}", level.name());
+            }
+            None => error!("Unknown log level {}, use error, warn, info, debug or trace", name),
+        },
+    }
+}
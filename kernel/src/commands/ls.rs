@@ -0,0 +1,14 @@
+use crate::{fs, println};
+
+crate::kernel_command!("ls", execute);
+
+pub fn execute() {
+    let files = fs::list();
+    if files.is_empty() {
+        println!("No files");
+        return;
+    }
+    for (name, size) in files {
+        println!("{:<32} {} B", name, size);
+    }
+}
@@ -0,0 +1,58 @@
+use crate::{allocator, memory, println};
+
+crate::kernel_command!("meminfo", execute);
+
+const FRAME_SIZE: usize = 4096;
+
+// `meminfo` shows how the kernel heap is used and how many physical frames are left.
+pub fn execute() {
+    let (used, free) = allocator::heap_usage();
+    let stats = allocator::heap_stats();
+
+    println!(
+        "Heap: {} KiB at {:#x}, grows up to {} KiB, {} allocator",
+        allocator::heap_size() / 1024,
+        allocator::HEAP_START,
+        allocator::heap_limit() / 1024,
+        allocator::allocator_name()
+    );
+    println!("  used {} bytes, free {} bytes", used, free);
+    println!(
+        "  {} live allocations, {} bytes (peak {} bytes), {} allocations since boot",
+        stats.live_allocations, stats.bytes_allocated, stats.peak_bytes, stats.total_allocations
+    );
+    // used heap that holds no live data: blocks rounded up to their size class and blocks parked in the lists
+    if used > 0 {
+        let wasted = used.saturating_sub(stats.bytes_allocated);
+        println!("  fragmentation: {}% of the used heap holds no live data ({} bytes)", wasted * 100 / used, wasted);
+    }
+
+    println!("  {} KiB mapped for large allocations", allocator::large::mapped_bytes() / 1024);
+
+    // only the fixed-size block allocator has block lists
+    if let Some(classes) = allocator::size_classes() {
+        println!("  {} allocations too large for a block went to the fallback allocator", stats.fallback_allocations);
+        println!("  block size   live   free");
+        for class in classes {
+            println!("  {:>10} {:>6} {:>6}", class.block_size, class.live, class.free);
+        }
+    }
+
+    // printing may allocate, which may need the frame allocator: don't hold it while printing
+    let frames = memory::FRAME_ALLOCATOR
+        .lock()
+        .as_ref()
+        .map(|frames| (frames.usable_frame_count(), frames.allocated_frame_count()));
+    match frames {
+        Some((usable, allocated)) => {
+            println!(
+                "Physical memory: {} usable frames ({} KiB), {} allocated, {} left",
+                usable,
+                usable * FRAME_SIZE / 1024,
+                allocated,
+                usable - allocated
+            );
+        }
+        None => println!("Physical memory: frame allocator not set up"),
+    }
+}
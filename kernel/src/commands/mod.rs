@@ -4,4 +4,16 @@ pub mod bsod;
 pub mod echo;
 pub mod fart;
 pub mod test;
-pub mod assert_eq;
\ No newline at end of file
+pub mod assert_eq;
+pub mod mode;
+pub mod gfxdemo;
+pub mod launcher;
+pub mod ls;
+pub mod cat;
+pub mod edit;
+pub mod dmesg;
+pub mod loglevel;
+pub mod bench;
+pub mod meminfo;
+pub mod slabinfo;
+pub mod leaks;
@@ -0,0 +1,17 @@
+use crate::{error, println};
+use crate::vga_buffer::mode::{self, TextMode};
+
+crate::kernel_command_with_args!("mode", execute);
+
+pub fn execute(args: &str) {
+    match TextMode::from_name(args.trim()) {
+        Some(text_mode) => {
+            mode::set_text_mode(text_mode);
+            println!("Switched to {} text mode", text_mode.name());
+        }
+        None => {
+            error!("Unknown mode {}, supported modes: 80x25 80x50 90x60", args.trim());
+            println!("Current mode: {}", mode::active_mode().name());
+        }
+    }
+}
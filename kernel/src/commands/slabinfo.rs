@@ -0,0 +1,29 @@
+use crate::{allocator::slab, error, println};
+
+crate::kernel_command_with_optional_args!("slabinfo", execute);
+
+// `slabinfo` lists the slab caches, `slabinfo reclaim` gives their empty slabs back.
+pub fn execute(args: &str) {
+    match args.trim() {
+        "" => list_caches(),
+        "reclaim" => println!("{} empty slabs given back", slab::reclaim_all()),
+        _ => error!("Usage: slabinfo [reclaim]"),
+    }
+}
+
+fn list_caches() {
+    println!("{:<16} {:>6} {:>6} {:>8} {:>6} {:>6} {:>8}", "cache", "size", "active", "objects", "slabs", "empty", "allocs");
+    for cache in slab::caches() {
+        let stats = cache.stats();
+        println!(
+            "{:<16} {:>6} {:>6} {:>8} {:>6} {:>6} {:>8}",
+            cache.name(),
+            stats.object_size,
+            stats.active_objects,
+            stats.slabs * stats.objects_per_slab,
+            stats.slabs,
+            stats.empty_slabs,
+            stats.allocations
+        );
+    }
+}
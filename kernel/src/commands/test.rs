@@ -1,9 +1,37 @@
-use crate::test_registry;
+use crate::{println, test_registry::{self, Filter}};
+use alloc::{format, string::String};
 
+crate::kernel_command_with_optional_args!("test", execute);
+
+// `test` lists the tests, `test all` runs all of them, `test heap*` the ones matching a glob,
+// `test tag:heap` the ones tagged heap and `test <name>` a single one.
 pub fn execute(args: &str) {
-    handle_test_command(args);
+    handle_test_command(args.trim());
 }
 
 fn handle_test_command(test_file: &str) {
-    test_registry::run_test(test_file);
-}
\ No newline at end of file
+    match test_file {
+        "" => list_tests(),
+        "all" => test_registry::run_matching(Filter::Glob(String::from("*"))),
+        tag if tag.starts_with("tag:") => test_registry::run_matching(Filter::Tag(String::from(&tag["tag:".len()..]))),
+        pattern if pattern.contains(|c| c == '*' || c == '?') => test_registry::run_matching(Filter::Glob(String::from(pattern))),
+        name => test_registry::run_test(name),
+    }
+}
+
+fn list_tests() {
+    println!("Usage: test all | test <glob> | test tag:<tag> | test <name>");
+    for (name, attributes) in test_registry::tests() {
+        let mut notes = String::new();
+        for tag in attributes.tags {
+            notes.push_str(&format!(" #{}", tag));
+        }
+        if attributes.should_panic.is_some() {
+            notes.push_str(" [should panic]");
+        }
+        if attributes.ignore {
+            notes.push_str(" [ignored]");
+        }
+        println!("  {}{}", name, notes);
+    }
+}
@@ -0,0 +1,144 @@
+use crate::{print, println, graphics, command_registry::run_command, task::keyboard, vga_buffer::Writer};
+use core::sync::atomic::{AtomicUsize, Ordering};
+use spin::Mutex;
+use x86_64::instructions::interrupts;
+
+// Virtual consoles: every console has its own off-screen Writer (buffer, cursor, color)
+// and its own input line. Only the active one is blitted to VGA memory, Alt+F1..F4 switches.
+
+pub const CONSOLE_COUNT: usize = 4;
+pub const INPUT_BUFFER_SIZE: usize = 256; // Maximum command length
+
+static ACTIVE_CONSOLE: AtomicUsize = AtomicUsize::new(0);
+
+struct InputLine {
+    buffer: [u8; INPUT_BUFFER_SIZE],
+    position: usize,
+}
+
+impl InputLine {
+    const fn new() -> Self {
+        InputLine {
+            buffer: [0; INPUT_BUFFER_SIZE],
+            position: 0,
+        }
+    }
+}
+
+// the writers are sized for the largest text mode, so keep them in static memory instead of building them on the stack
+static WRITERS: [Mutex<Writer>; CONSOLE_COUNT] = {
+    let mut writers = [const { Mutex::new(Writer::new(false)) }; CONSOLE_COUNT];
+    writers[0] = Mutex::new(Writer::new(true)); // console 0 is shown at boot
+    writers
+};
+static INPUT_LINES: [Mutex<InputLine>; CONSOLE_COUNT] = [const { Mutex::new(InputLine::new()) }; CONSOLE_COUNT];
+
+/// Blits console 0 over whatever the BIOS left on the screen.
+pub fn init() {
+    interrupts::without_interrupts(|| {
+        WRITERS[0].lock().present();
+    });
+}
+
+/// Returns the index of the console that is currently shown.
+pub fn active() -> usize {
+    ACTIVE_CONSOLE.load(Ordering::SeqCst)
+}
+
+/// Returns the writer of the given console.
+///
+/// Panics if `console` is not below `CONSOLE_COUNT`.
+pub fn writer(console: usize) -> &'static Mutex<Writer> {
+    &WRITERS[console]
+}
+
+/// Returns the writer of the console that is currently shown.
+pub fn active_writer() -> &'static Mutex<Writer> {
+    writer(active())
+}
+
+/// Makes the given console the visible one and blits its buffer to the screen.
+pub fn switch_to(console: usize) {
+    if console >= CONSOLE_COUNT || graphics::is_active() {
+        return; // the text consoles can't be shown while a graphics mode is up
+    }
+
+    interrupts::without_interrupts(|| {
+        let previous = ACTIVE_CONSOLE.swap(console, Ordering::SeqCst);
+        if previous == console {
+            return;
+        }
+        WRITERS[previous].lock().set_visible(false);
+        WRITERS[console].lock().set_visible(true);
+    });
+}
+
+/// Resizes every console after a text mode switch.
+pub fn resize_all(width: usize, height: usize) {
+    interrupts::without_interrupts(|| {
+        for writer in WRITERS.iter() {
+            writer.lock().resize(width, height);
+        }
+    });
+}
+
+/// Prints the shell prompt on the active console.
+pub fn print_prompt() {
+    print!("> ");
+}
+
+/// Feeds a typed character into the input line of the active console.
+///
+/// Enter runs the collected command, backspace removes the last character,
+/// everything else is echoed and appended to the line.
+pub fn handle_char(character: char) {
+    let console = active();
+
+    if character == '\n' {
+        // Enter key pressed
+        println!(""); // Move to a new line
+
+        // Take the command out of the buffer, so the line is free while the command runs
+        let mut command = [0; INPUT_BUFFER_SIZE];
+        let length = {
+            let mut line = INPUT_LINES[console].lock();
+            let length = line.position;
+            command[..length].copy_from_slice(&line.buffer[..length]);
+            line.position = 0;
+            length
+        };
+
+        // Handle the command
+        run_command(core::str::from_utf8(&command[..length]).unwrap_or(""));
+
+        // Print the prompt for the next command, unless the command took over the keyboard.
+        // Full-screen programs get it back from `keyboard` when they are done.
+        if !keyboard::is_grabbed() {
+            print_prompt();
+        }
+    } else if character == '\x08' {
+        // Backspace key pressed
+        interrupts::without_interrupts(|| {
+            WRITERS[console].lock().remove_previous_symbol();
+        });
+        crate::serial_print!("\x08 \x08"); // erase it in the serial terminal too
+        // drop the whole last character, skipping UTF-8 continuation bytes
+        let mut line = INPUT_LINES[console].lock();
+        while line.position > 0 {
+            line.position -= 1;
+            if line.buffer[line.position] & 0xC0 != 0x80 {
+                break;
+            }
+        }
+    } else if character.is_control() && character != '\t' {
+        // Ctrl+letter and friends only mean something to full-screen programs
+    } else {
+        print!("{}", character);
+        let mut line = INPUT_LINES[console].lock();
+        let position = line.position;
+        if position + character.len_utf8() <= INPUT_BUFFER_SIZE {
+            character.encode_utf8(&mut line.buffer[position..]);
+            line.position += character.len_utf8();
+        }
+    }
+}
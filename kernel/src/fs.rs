@@ -0,0 +1,56 @@
+use crate::allocator::slab::{SlabBox, SlabCache};
+use alloc::{collections::BTreeMap, string::String, vec::Vec};
+use spin::Mutex;
+
+// A flat in-memory filesystem: file name -> contents. Everything is lost on reboot,
+// but it is enough to write scripts and configs from inside the kernel.
+
+pub const MAX_NAME_LENGTH: usize = 32;
+
+// a file, the nodes come from their own slab cache
+struct Node {
+    data: Vec<u8>,
+}
+
+static NODES: SlabCache = SlabCache::of::<Node>("fs_node");
+static FILES: Mutex<BTreeMap<String, SlabBox<Node>>> = Mutex::new(BTreeMap::new());
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FsError {
+    NotFound,
+    InvalidName,
+    NoSpace,
+}
+
+/// Names can't be empty, too long or contain whitespace, since the shell splits arguments on it.
+pub fn is_valid_name(name: &str) -> bool {
+    !name.is_empty() && name.len() <= MAX_NAME_LENGTH && !name.contains(char::is_whitespace)
+}
+
+/// Returns a copy of the file contents.
+pub fn read(name: &str) -> Result<Vec<u8>, FsError> {
+    FILES.lock().get(name).map(|node| node.data.clone()).ok_or(FsError::NotFound)
+}
+
+/// Creates the file or replaces its contents.
+pub fn write(name: &str, data: &[u8]) -> Result<(), FsError> {
+    if !is_valid_name(name) {
+        return Err(FsError::InvalidName);
+    }
+    let node = SlabBox::new_in(Node { data: Vec::from(data) }, &NODES).ok_or(FsError::NoSpace)?;
+    FILES.lock().insert(String::from(name), node);
+    Ok(())
+}
+
+pub fn remove(name: &str) -> Result<(), FsError> {
+    FILES.lock().remove(name).map(|_| ()).ok_or(FsError::NotFound)
+}
+
+pub fn exists(name: &str) -> bool {
+    FILES.lock().contains_key(name)
+}
+
+/// Returns every file name with its size in bytes, sorted by name.
+pub fn list() -> Vec<(String, usize)> {
+    FILES.lock().iter().map(|(name, node)| (name.clone(), node.data.len())).collect()
+}
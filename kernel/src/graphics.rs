@@ -0,0 +1,319 @@
+use crate::{memory, vga_buffer::{cp437, mode::{self, RegisterSet}}};
+use core::{fmt, sync::atomic::{AtomicBool, Ordering}};
+use spin::Mutex;
+use x86_64::{instructions::{interrupts, port::Port}, PhysAddr};
+
+// VGA mode 13h: 320x200 pixels, one byte per pixel indexing a 256 color palette,
+// linear framebuffer at physical 0xa0000.
+
+pub const WIDTH: usize = 320;
+pub const HEIGHT: usize = 200;
+const FRAMEBUFFER_ADDRESS: u64 = 0xa0000;
+
+const VGA_DAC_READ_INDEX: u16 = 0x3C7;
+const VGA_DAC_WRITE_INDEX: u16 = 0x3C8;
+const VGA_DAC_DATA: u16 = 0x3C9;
+
+const REGS_320X200X256: RegisterSet = [
+    // MISC
+    0x63,
+    // SEQ: chain-4, so every byte is one pixel
+    0x03, 0x01, 0x0F, 0x00, 0x0E,
+    // CRTC
+    0x5F, 0x4F, 0x50, 0x82, 0x54, 0x80, 0xBF, 0x1F,
+    0x00, 0x41, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0x9C, 0x0E, 0x8F, 0x28, 0x40, 0x96, 0xB9, 0xA3,
+    0xFF,
+    // GC
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x40, 0x05, 0x0F,
+    0xFF,
+    // AC
+    0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07,
+    0x08, 0x09, 0x0A, 0x0B, 0x0C, 0x0D, 0x0E, 0x0F,
+    0x41, 0x00, 0x0F, 0x00, 0x00,
+];
+
+// the 16 text mode colors as 6 bit DAC values, so `Color as u8` works as a pixel color too
+const TEXT_COLORS: [[u8; 3]; 16] = [
+    [0, 0, 0], [0, 0, 42], [0, 42, 0], [0, 42, 42],
+    [42, 0, 0], [42, 0, 42], [42, 21, 0], [42, 42, 42],
+    [21, 21, 21], [21, 21, 63], [21, 63, 21], [21, 63, 63],
+    [63, 21, 21], [63, 21, 63], [63, 63, 21], [63, 63, 63],
+];
+
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+static SAVED_PALETTE: Mutex<[u8; 256 * 3]> = Mutex::new([0; 256 * 3]);
+
+/// Returns true while mode 13h is up and the text consoles are hidden.
+pub fn is_active() -> bool {
+    ACTIVE.load(Ordering::SeqCst)
+}
+
+/// Switches to mode 13h and installs a palette of the 16 text colors, a 6x6x6 color cube and a gray ramp.
+pub fn enter() {
+    interrupts::without_interrupts(|| {
+        if ACTIVE.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        read_palette(&mut SAVED_PALETTE.lock());
+        unsafe { mode::write_registers(&REGS_320X200X256) };
+        write_palette(&default_palette());
+    });
+    Framebuffer::new().clear(0);
+}
+
+/// Goes back to the active text mode and redraws the visible console.
+pub fn leave() {
+    interrupts::without_interrupts(|| {
+        if !ACTIVE.swap(false, Ordering::SeqCst) {
+            return;
+        }
+        write_palette(&SAVED_PALETTE.lock());
+    });
+    // reloads the font (mode 13h writes over plane 2) and blits the visible console again
+    mode::restore_text_mode();
+}
+
+/// Returns the palette index closest to the given color, each channel 0..=255.
+pub fn rgb(red: u8, green: u8, blue: u8) -> u8 {
+    let level = |channel: u8| (channel as u16 * 5 / 255) as u8;
+    16 + 36 * level(red) + 6 * level(green) + level(blue)
+}
+
+fn default_palette() -> [u8; 256 * 3] {
+    let mut palette = [0; 256 * 3];
+    for (index, color) in TEXT_COLORS.iter().enumerate() {
+        palette[index * 3..index * 3 + 3].copy_from_slice(color);
+    }
+    // 16..232: 6x6x6 color cube
+    for index in 0..216 {
+        let entry = (16 + index) * 3;
+        palette[entry] = (index / 36) as u8 * 63 / 5;
+        palette[entry + 1] = (index / 6 % 6) as u8 * 63 / 5;
+        palette[entry + 2] = (index % 6) as u8 * 63 / 5;
+    }
+    // 232..256: gray ramp
+    for index in 0..24 {
+        let entry = (232 + index) * 3;
+        let level = (index * 63 / 23) as u8;
+        palette[entry..entry + 3].copy_from_slice(&[level, level, level]);
+    }
+    palette
+}
+
+fn read_palette(palette: &mut [u8; 256 * 3]) {
+    unsafe {
+        Port::<u8>::new(VGA_DAC_READ_INDEX).write(0);
+        let mut data = Port::<u8>::new(VGA_DAC_DATA);
+        for value in palette.iter_mut() {
+            *value = data.read();
+        }
+    }
+}
+
+fn write_palette(palette: &[u8; 256 * 3]) {
+    unsafe {
+        Port::<u8>::new(VGA_DAC_WRITE_INDEX).write(0);
+        let mut data = Port::<u8>::new(VGA_DAC_DATA);
+        for &value in palette.iter() {
+            data.write(value);
+        }
+    }
+}
+
+/// Drawing primitives on the mode 13h framebuffer. Coordinates outside the screen are clipped.
+pub struct Framebuffer {
+    pixels: *mut u8,
+}
+
+impl Framebuffer {
+    pub fn new() -> Framebuffer {
+        Framebuffer {
+            pixels: memory::phys_to_virt(PhysAddr::new(FRAMEBUFFER_ADDRESS)).as_mut_ptr(),
+        }
+    }
+
+    pub fn clear(&mut self, color: u8) {
+        self.fill_rect(0, 0, WIDTH, HEIGHT, color);
+    }
+
+    pub fn put_pixel(&mut self, x: usize, y: usize, color: u8) {
+        if x < WIDTH && y < HEIGHT {
+            unsafe { self.pixels.add(y * WIDTH + x).write_volatile(color) };
+        }
+    }
+
+    pub fn get_pixel(&self, x: usize, y: usize) -> u8 {
+        if x < WIDTH && y < HEIGHT {
+            unsafe { self.pixels.add(y * WIDTH + x).read_volatile() }
+        } else {
+            0
+        }
+    }
+
+    /// Draws a line with Bresenham's algorithm.
+    pub fn line(&mut self, x0: isize, y0: isize, x1: isize, y1: isize, color: u8) {
+        let dx = (x1 - x0).abs();
+        let dy = -(y1 - y0).abs();
+        let step_x = if x0 < x1 { 1 } else { -1 };
+        let step_y = if y0 < y1 { 1 } else { -1 };
+        let (mut x, mut y) = (x0, y0);
+        let mut error = dx + dy;
+
+        loop {
+            if x >= 0 && y >= 0 {
+                self.put_pixel(x as usize, y as usize, color);
+            }
+            if x == x1 && y == y1 {
+                break;
+            }
+            let doubled = 2 * error;
+            if doubled >= dy {
+                error += dy;
+                x += step_x;
+            }
+            if doubled <= dx {
+                error += dx;
+                y += step_y;
+            }
+        }
+    }
+
+    /// Draws the outline of a rectangle.
+    pub fn rect(&mut self, x: usize, y: usize, width: usize, height: usize, color: u8) {
+        if width == 0 || height == 0 {
+            return;
+        }
+        let (right, bottom) = (x + width - 1, y + height - 1);
+        for column in x..=right {
+            self.put_pixel(column, y, color);
+            self.put_pixel(column, bottom, color);
+        }
+        for row in y..=bottom {
+            self.put_pixel(x, row, color);
+            self.put_pixel(right, row, color);
+        }
+    }
+
+    pub fn fill_rect(&mut self, x: usize, y: usize, width: usize, height: usize, color: u8) {
+        for row in y..(y + height).min(HEIGHT) {
+            for column in x..(x + width).min(WIDTH) {
+                self.put_pixel(column, row, color);
+            }
+        }
+    }
+
+    /// Copies a `width` pixels wide image to (x, y). Pixels equal to `transparent` are skipped.
+    pub fn blit(&mut self, x: usize, y: usize, width: usize, image: &[u8], transparent: Option<u8>) {
+        if width == 0 || image.is_empty() {
+            return;
+        }
+        for (index, &color) in image.iter().enumerate() {
+            if Some(color) != transparent {
+                self.put_pixel(x + index % width, y + index / width, color);
+            }
+        }
+    }
+
+    /// Draws an 8x8 glyph, `background` of `None` leaves the pixels behind it alone.
+    pub fn draw_glyph(&mut self, x: usize, y: usize, character: u8, foreground: u8, background: Option<u8>) {
+        let glyph = mode::glyph_8x8(character);
+        for (line, bits) in glyph.iter().enumerate() {
+            for bit in 0..8 {
+                if bits & (0x80 >> bit) != 0 {
+                    self.put_pixel(x + bit, y + line, foreground);
+                } else if let Some(background) = background {
+                    self.put_pixel(x + bit, y + line, background);
+                }
+            }
+        }
+    }
+
+    // moves everything up by `lines` pixel rows and clears the rows that became free
+    fn scroll_up(&mut self, lines: usize, color: u8) {
+        for y in lines..HEIGHT {
+            for x in 0..WIDTH {
+                let pixel = self.get_pixel(x, y);
+                self.put_pixel(x, y - lines, pixel);
+            }
+        }
+        self.fill_rect(0, HEIGHT - lines, WIDTH, lines, color);
+    }
+}
+
+const GLYPH_SIZE: usize = 8;
+pub const TEXT_COLUMNS: usize = WIDTH / GLYPH_SIZE;
+pub const TEXT_ROWS: usize = HEIGHT / GLYPH_SIZE;
+
+/// Text output on the framebuffer with the 8x8 font, a 40x25 console that implements
+/// `fmt::Write` just like `vga_buffer::Writer`.
+pub struct GraphicsWriter {
+    framebuffer: Framebuffer,
+    column: usize,
+    row: usize,
+    foreground: u8,
+    background: u8,
+}
+
+impl GraphicsWriter {
+    pub fn new(foreground: u8, background: u8) -> GraphicsWriter {
+        GraphicsWriter {
+            framebuffer: Framebuffer::new(),
+            column: 0,
+            row: 0,
+            foreground,
+            background,
+        }
+    }
+
+    pub fn set_colors(&mut self, foreground: u8, background: u8) {
+        self.foreground = foreground;
+        self.background = background;
+    }
+
+    pub fn set_position(&mut self, column: usize, row: usize) {
+        self.column = column.min(TEXT_COLUMNS);
+        self.row = row.min(TEXT_ROWS - 1);
+    }
+
+    /// Writes a CP437 byte. Wraps at the right edge and scrolls at the bottom.
+    pub fn write_byte(&mut self, byte: u8) {
+        match byte {
+            b'\n' => self.new_line(),
+            byte => {
+                if self.column >= TEXT_COLUMNS {
+                    self.new_line();
+                }
+                self.framebuffer.draw_glyph(
+                    self.column * GLYPH_SIZE,
+                    self.row * GLYPH_SIZE,
+                    byte,
+                    self.foreground,
+                    Some(self.background),
+                );
+                self.column += 1;
+            }
+        }
+    }
+
+    fn new_line(&mut self) {
+        self.column = 0;
+        if self.row + 1 < TEXT_ROWS {
+            self.row += 1;
+        } else {
+            self.framebuffer.scroll_up(GLYPH_SIZE, self.background);
+        }
+    }
+}
+
+impl fmt::Write for GraphicsWriter {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for character in s.chars() {
+            match character {
+                '\n' => self.write_byte(b'\n'),
+                character => self.write_byte(cp437::to_glyph(character)),
+            }
+        }
+        Ok(())
+    }
+}
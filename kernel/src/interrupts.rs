@@ -13,6 +13,7 @@ pub const PIC_2_OFFSET: u8 = PIC_1_OFFSET + 8;
 pub enum InterruptIndex {
     Timer = PIC_1_OFFSET,
     Keyboard,
+    Serial = PIC_1_OFFSET + 4, // COM1
 }
 
 impl InterruptIndex {
@@ -44,6 +45,9 @@ lazy_static! {
         idt[InterruptIndex::Keyboard.as_usize()]
             .set_handler_fn(keyboard_interrupt_handler);
 
+        idt[InterruptIndex::Serial.as_usize()]
+            .set_handler_fn(serial_interrupt_handler);
+
         idt.page_fault.set_handler_fn(page_fault_handler);
 
         idt
@@ -77,10 +81,15 @@ extern "x86-interrupt" fn double_fault_handler(
 }
 
 extern "x86-interrupt" fn timer_interrupt_handler(_stack_frame: InterruptStackFrame) {
+    crate::time::on_tick();
+
     unsafe {
         PICS.lock()
             .notify_end_of_interrupt(InterruptIndex::Timer.as_u8()); // need to notify the end so system can process the next interrupt
     }
+
+    // flags a test that ran out of time, it is stopped at its next checkpoint
+    crate::test_registry::check_timeout();
 }
 
 extern "x86-interrupt" fn keyboard_interrupt_handler(_stack_frame: InterruptStackFrame) {
@@ -94,4 +103,24 @@ extern "x86-interrupt" fn keyboard_interrupt_handler(_stack_frame: InterruptStac
         PICS.lock()
             .notify_end_of_interrupt(InterruptIndex::Keyboard.as_u8()); // process the next interrupt
     }
-}
\ No newline at end of file
+}
+
+extern "x86-interrupt" fn serial_interrupt_handler(_stack_frame: InterruptStackFrame) {
+    // the FIFO only interrupts once per burst (or after a timeout), so empty it completely;
+    // the bytes are queued after the UART is unlocked, queueing may log and so print to it
+    let mut buffer = [0; crate::serial::FIFO_SIZE];
+    loop {
+        let count = crate::serial::receive_pending(&mut buffer);
+        for &byte in &buffer[..count] {
+            crate::task::serial::add_byte(byte);
+        }
+        if count < buffer.len() {
+            break;
+        }
+    }
+
+    unsafe {
+        PICS.lock()
+            .notify_end_of_interrupt(InterruptIndex::Serial.as_u8());
+    }
+}
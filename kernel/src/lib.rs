@@ -3,6 +3,7 @@
 #![feature(custom_test_frameworks)]
 #![feature(const_mut_refs)]
 #![feature(abi_x86_interrupt)]
+#![feature(linkage)]
 #![test_runner(crate::test_runner)]
 #![reexport_test_harness_main = "test_main"]
 
@@ -13,25 +14,40 @@ pub mod allocator;
 pub mod gdt;
 pub mod interrupts;
 pub mod vga_buffer;
+pub mod console;
+pub mod serial;
+pub mod qemu;
+pub mod log;
+pub mod graphics;
+pub mod status_bar;
+pub mod time;
 pub mod command_registry;
 pub mod sound;
 pub mod memory;
 pub mod test_registry;
+pub mod bench_registry;
+pub mod recovery;
 pub mod task;
 pub mod commands;
+pub mod tui;
+pub mod fs;
 
 // lib.rs mostly consists of implementing tests using cargo test, since I implemened test_registry.rs, this lib.rs is used only for initializing GDT and Interrupts
 
 pub fn init() {
+    allocator::debug::record_stack_top();
     gdt::init();
+    console::init();
+    vga_buffer::mode::init();
     interrupts::init_idt();
+    time::init();
     unsafe { interrupts::PICS.lock().initialize() };
+    serial::init();
     x86_64::instructions::interrupts::enable();
 }
 
 #[cfg(test)]
 use bootloader:: {BootInfo, entry_point};
-use commands::bsod::handle_bsod;
 
 #[cfg(test)]
 entry_point!(test_kernel_main);
@@ -43,9 +59,23 @@ fn test_kernel_main(_boot_info: &'static BootInfo) -> ! {
     hlt_loop();
 }
 
+/// Reports the failed test over serial and exits QEMU with a failure code.
+/// Serial only: the panic may have happened while the VGA writer was locked.
 pub fn test_panic_handler(info: &PanicInfo) -> ! {
-    handle_bsod(info);
-    hlt_loop();
+    serial_println!("[failed]");
+    serial_println!("Error: {}", info);
+    qemu::exit(qemu::QemuExitCode::Failed);
+}
+
+/// The entries the linker collected into a section, given its `__start_`/`__stop_` symbols.
+///
+/// Safety: both pointers have to come from the same section holding only `T`s (or both be null).
+pub(crate) unsafe fn linker_section<T>(start: *const u8, stop: *const u8) -> &'static [T] {
+    let (start, stop) = (start as *const T, stop as *const T);
+    if start.is_null() || stop <= start {
+        return &[];
+    }
+    core::slice::from_raw_parts(start, stop.offset_from(start) as usize)
 }
 
 // halting
@@ -61,11 +91,13 @@ fn panic(info: &PanicInfo) -> ! {
     test_panic_handler(info)
 }
 
+// output goes to COM1 too, so `cargo test` shows the results and gets QEMU's exit code
 pub fn test_runner(tests: &[&dyn Fn()]) { 
     println!("Running {} tests", tests.len());
     for test in tests {
         test.run();
     }
+    qemu::exit(qemu::QemuExitCode::Success);
 }
 
 pub trait Testable {
@@ -79,7 +111,7 @@ where
     fn run(&self) {
         print!("{}...\t", core::any::type_name::<T>());
         self();
-        println!("[ok]");
+        println_colored!(vga_buffer::Color::LightGreen, "[ok]");
     }
 }
 
@@ -0,0 +1,211 @@
+use crate::time;
+use alloc::vec::Vec;
+use core::fmt::{self, Write};
+use core::sync::atomic::{AtomicU8, Ordering};
+use spin::Mutex;
+use x86_64::instructions::interrupts;
+
+// Kernel log: messages go into a fixed-size ring buffer (read with `dmesg`) and to COM1,
+// instead of being printed into whatever console the user is typing in.
+// Nothing here allocates, so it can be used from interrupt handlers.
+
+/// How many records the ring buffer keeps before overwriting the oldest.
+pub const LOG_CAPACITY: usize = 128;
+/// Longer messages are cut off.
+pub const MESSAGE_LENGTH: usize = 120;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[repr(u8)]
+pub enum Level {
+    Error = 1,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl Level {
+    pub const ALL: [Level; 5] = [Level::Error, Level::Warn, Level::Info, Level::Debug, Level::Trace];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Level::Error => "error",
+            Level::Warn => "warn",
+            Level::Info => "info",
+            Level::Debug => "debug",
+            Level::Trace => "trace",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Level> {
+        Level::ALL.iter().copied().find(|level| level.name() == name)
+    }
+
+    fn from_u8(value: u8) -> Level {
+        Level::ALL[(value as usize).clamp(1, Level::ALL.len()) - 1]
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct Record {
+    pub level: Level,
+    pub ticks: u64, // time::ticks() when the message was logged
+    length: usize,
+    message: [u8; MESSAGE_LENGTH],
+}
+
+impl Record {
+    const EMPTY: Record = Record {
+        level: Level::Trace,
+        ticks: 0,
+        length: 0,
+        message: [0; MESSAGE_LENGTH],
+    };
+
+    /// A record of `args` at `level`, cut off after MESSAGE_LENGTH bytes.
+    pub fn new(level: Level, ticks: u64, args: fmt::Arguments) -> Record {
+        let mut record = Record {
+            level,
+            ticks,
+            ..Record::EMPTY
+        };
+        let _ = record.write_fmt(args);
+        record
+    }
+
+    pub fn message(&self) -> &str {
+        // `write_str` only cuts at char boundaries
+        core::str::from_utf8(&self.message[..self.length]).unwrap_or("")
+    }
+}
+
+impl Write for Record {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for character in s.chars() {
+            let length = character.len_utf8();
+            if self.length + length > MESSAGE_LENGTH {
+                break;
+            }
+            character.encode_utf8(&mut self.message[self.length..]);
+            self.length += length;
+        }
+        Ok(())
+    }
+}
+
+/// Keeps the last `N` records, the kernel log is one with LOG_CAPACITY records.
+pub struct LogBuffer<const N: usize> {
+    records: [Record; N],
+    next: usize,  // slot the next record goes into
+    count: usize, // stored records, at most N
+}
+
+impl<const N: usize> LogBuffer<N> {
+    pub const fn new() -> Self {
+        LogBuffer {
+            records: [Record::EMPTY; N],
+            next: 0,
+            count: 0,
+        }
+    }
+
+    /// Stores `record`, overwriting the oldest one if the buffer is full.
+    pub fn push(&mut self, record: Record) {
+        self.records[self.next] = record;
+        self.next = (self.next + 1) % N;
+        self.count = (self.count + 1).min(N);
+    }
+
+    /// Returns the stored records at `level` or more important, oldest first.
+    pub fn records(&self, level: Level) -> Vec<Record> {
+        let oldest = (self.next + N - self.count) % N;
+        (0..self.count)
+            .map(|index| self.records[(oldest + index) % N])
+            .filter(|record| record.level <= level)
+            .collect()
+    }
+
+    pub fn clear(&mut self) {
+        self.next = 0;
+        self.count = 0;
+    }
+}
+
+static LOG: Mutex<LogBuffer<LOG_CAPACITY>> = Mutex::new(LogBuffer::new());
+
+// records less important than this are dropped
+static MAX_LEVEL: AtomicU8 = AtomicU8::new(Level::Info as u8);
+
+/// The most verbose level that is still recorded.
+pub fn max_level() -> Level {
+    Level::from_u8(MAX_LEVEL.load(Ordering::Relaxed))
+}
+
+pub fn set_max_level(level: Level) {
+    MAX_LEVEL.store(level as u8, Ordering::Relaxed);
+}
+
+pub fn enabled(level: Level) -> bool {
+    level <= max_level()
+}
+
+#[doc(hidden)]
+pub fn _log(level: Level, args: fmt::Arguments) {
+    if !enabled(level) {
+        return;
+    }
+
+    let record = Record::new(level, time::ticks(), args);
+    interrupts::without_interrupts(|| LOG.lock().push(record));
+
+    crate::serial_println!("[{}] {}: {}", Timestamp(record.ticks), level.name(), record.message());
+}
+
+/// Returns the stored records at `level` or more important, oldest first.
+pub fn records(level: Level) -> Vec<Record> {
+    interrupts::without_interrupts(|| LOG.lock().records(level))
+}
+
+pub fn clear() {
+    interrupts::without_interrupts(|| LOG.lock().clear());
+}
+
+/// Formats timer ticks as seconds since boot, like `   12.34`.
+pub struct Timestamp(pub u64);
+
+impl fmt::Display for Timestamp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let hundredths = self.0 * 100 / time::TIMER_HZ;
+        write!(f, "{:5}.{:02}", hundredths / 100, hundredths % 100)
+    }
+}
+
+#[macro_export]
+macro_rules! log {
+    ($level:expr, $($arg:tt)*) => ($crate::log::_log($level, format_args!($($arg)*)));
+}
+
+#[macro_export]
+macro_rules! log_error {
+    ($($arg:tt)*) => ($crate::log!($crate::log::Level::Error, $($arg)*));
+}
+
+#[macro_export]
+macro_rules! log_warn {
+    ($($arg:tt)*) => ($crate::log!($crate::log::Level::Warn, $($arg)*));
+}
+
+#[macro_export]
+macro_rules! log_info {
+    ($($arg:tt)*) => ($crate::log!($crate::log::Level::Info, $($arg)*));
+}
+
+#[macro_export]
+macro_rules! log_debug {
+    ($($arg:tt)*) => ($crate::log!($crate::log::Level::Debug, $($arg)*));
+}
+
+#[macro_export]
+macro_rules! log_trace {
+    ($($arg:tt)*) => ($crate::log!($crate::log::Level::Trace, $($arg)*));
+}
@@ -4,19 +4,16 @@
 #![test_runner(kernel::test_runner)]
 #![reexport_test_harness_main = "test_main"]
 
-mod vga_buffer;
-
 extern crate alloc;
 use core::panic::PanicInfo;
 use bootloader::{BootInfo, entry_point};
-use kernel::command_registry;
+use kernel::{command_registry, console, status_bar, print, println, print_to};
 
-use kernel::vga_buffer::WRITER;
 use alloc::boxed::Box;
 use alloc::vec::Vec;
-use kernel::{allocator::HEAP_SIZE, test_registry};
-use kernel::task::{Task, keyboard, executor::Executor};
-use kernel::commands::{bsod::{handle_bsod, self}, fart, echo, test, assert_eq};
+use kernel::{allocator::HEAP_SIZE, kernel_bench, kernel_test, bench_registry, test_registry::{self, TestAttributes}};
+use kernel::task::{Task, keyboard, serial, executor::Executor};
+use kernel::commands::bsod::handle_bsod;
 
 
 // b"string" means to convert the string into bytes
@@ -26,7 +23,7 @@ use kernel::commands::{bsod::{handle_bsod, self}, fart, echo, test, assert_eq};
 entry_point!(kernel_main); 
 fn kernel_main(boot_info: &'static BootInfo) -> ! {
     use kernel::allocator;
-    use kernel::memory::{self, BootInfoFrameAllocator};
+    use kernel::memory::{self, BitmapFrameAllocator};
     use x86_64::{structures::paging::Page, VirtAddr};
     
     println!("Basic Kernel Implementation");
@@ -35,7 +32,7 @@ fn kernel_main(boot_info: &'static BootInfo) -> ! {
     
     // Sync writer's position
     {        
-        let mut writer = WRITER.lock();
+        let mut writer = console::active_writer().lock();
         writer.set_column(2); // after > symbol
     }   
 
@@ -44,7 +41,7 @@ fn kernel_main(boot_info: &'static BootInfo) -> ! {
     let phys_mem_offset = VirtAddr::new(boot_info.physical_memory_offset);
     let mut mapper = unsafe { memory::init(phys_mem_offset) };
     let mut frame_allocator = unsafe {
-        BootInfoFrameAllocator::init(&boot_info.memory_map)
+        BitmapFrameAllocator::init(&boot_info.memory_map)
     };
 
     // new
@@ -53,6 +50,9 @@ fn kernel_main(boot_info: &'static BootInfo) -> ! {
     // map an unused page
     let page = Page::containing_address(VirtAddr::new(0));
     memory::create_example_mapping(page, &mut mapper, &mut frame_allocator);
+    // hand both over, so the heap can map more pages when it runs full
+    *memory::MAPPER.lock() = Some(mapper);
+    *memory::FRAME_ALLOCATOR.lock() = Some(frame_allocator);
 
     // // write the string `New!` to the screen through the new mapping
     // let page_ptr: *mut u64 = page.start_address().as_mut_ptr();
@@ -63,28 +63,23 @@ fn kernel_main(boot_info: &'static BootInfo) -> ! {
     #[cfg(test)] // only if we ran "cargo test"
     test_main();
     
-    // registering all tests...
-    test_registry::register_test("equal_test", trivial_assertion);
-    test_registry::register_test("fail_test", fail_test);
-    test_registry::register_test("large_vec", large_vec);
-    test_registry::register_test("many_boxes_long_lived", many_boxes_long_lived);
-    test_registry::register_test("simple_alloc", simple_allocation);
-    test_registry::register_test("many_boxes", many_boxes);
-    test_registry::register_test("simple_println", println_simple);
-    test_registry::register_test("many_println", println_many);
-
-    // registering commands
-    command_registry::register_command("bsod", bsod::execute);
-    command_registry::register_command("fart", fart::execute);
-    command_registry::register_command_with_args("echo", echo::execute);
-    command_registry::register_command_with_args("test", test::execute);
-    command_registry::register_command_with_args("assert_eq", assert_eq::execute);
+    // tests, benchmarks and commands register themselves with kernel_test!, kernel_bench! and kernel_command!
+    test_registry::register_linked_tests();
+    bench_registry::register_linked_benches();
+    command_registry::register_linked_commands();
 
     println!("Checking state... [ok]");
     print!("> ");
 
+    // the other virtual consoles get their own shell, switch with Alt+F1..F4
+    for index in 1..console::CONSOLE_COUNT {
+        print_to!(index, "VertexDOS console {}\n> ", index + 1);
+    }
+
     let mut executor = Executor::new();
     executor.spawn(Task::new(keyboard::print_keypress()));
+    executor.spawn(Task::new(serial::process_input()));
+    executor.spawn(Task::new(status_bar::run()));
     executor.run();
 }
 
@@ -94,6 +89,7 @@ fn kernel_main(boot_info: &'static BootInfo) -> ! {
 #[cfg(not(test))]
 #[panic_handler]
 fn panic(info: &PanicInfo) -> ! {
+    kernel::recovery::recover(info); // returns only if no test is catching panics
     handle_bsod(info);
     kernel::hlt_loop()
 }
@@ -108,10 +104,12 @@ fn panic(info: &PanicInfo) -> ! {
 fn trivial_assertion() {
     assert_eq!(1, 1);
 }
+kernel_test!("equal_test", trivial_assertion);
 
 fn fail_test() {
     assert_eq!(0, 1);
 }
+kernel_test!(fail_test, TestAttributes::new().should_panic("left == right"));
 
 // some heap allocation tests
 fn large_vec() {
@@ -122,6 +120,7 @@ fn large_vec() {
     }
     assert_eq!(vec.iter().sum::<u64>(), (n - 1) * n / 2);
 }
+kernel_test!(large_vec, TestAttributes::new().tags(&["heap"]));
 
 fn many_boxes_long_lived() {
     let long_lived = Box::new(1); // new
@@ -131,6 +130,7 @@ fn many_boxes_long_lived() {
     }
     assert_eq!(*long_lived, 1); // new
 }
+kernel_test!(many_boxes_long_lived, TestAttributes::new().tags(&["heap"]).timeout(3000));
 
 fn simple_allocation() {
     let heap_value_1 = Box::new(41);
@@ -138,6 +138,7 @@ fn simple_allocation() {
     assert_eq!(*heap_value_1, 41);
     assert_eq!(*heap_value_2, 13);
 }
+kernel_test!("simple_alloc", simple_allocation, TestAttributes::new().tags(&["heap"]));
 
 fn many_boxes() {
     for i in 0..HEAP_SIZE {
@@ -145,17 +146,497 @@ fn many_boxes() {
         assert_eq!(*x, i);
     }
 }
+kernel_test!(many_boxes, TestAttributes::new().tags(&["heap"]).timeout(3000));
+
+fn large_allocation_unmapped() {
+    use kernel::allocator::large;
+
+    let before = large::mapped_bytes();
+    let mut buffer = Vec::<u8>::with_capacity(256 * 1024);
+    buffer.resize(256 * 1024, 1);
+    #[cfg(not(feature = "debug-alloc"))]
+    assert_eq!(large::mapped_bytes(), before + 256 * 1024);
+    #[cfg(feature = "debug-alloc")]
+    assert_eq!(large::mapped_bytes(), before + 256 * 1024 + 4096); // header and red zones
+    assert_eq!(buffer.iter().map(|&byte| byte as usize).sum::<usize>(), 256 * 1024);
+    drop(buffer);
+    assert_eq!(large::mapped_bytes(), before);
+}
+kernel_test!(large_allocation_unmapped, TestAttributes::new().tags(&["heap"]));
+
+fn frame_reuse() {
+    use kernel::memory::FRAME_ALLOCATOR;
+    use x86_64::structures::paging::{FrameAllocator, FrameDeallocator};
+
+    // no allocating or asserting while holding the frame allocator, the heap may need it
+    let (first, again, free_before, free_during, free_after, run) = {
+        let mut guard = FRAME_ALLOCATOR.lock();
+        let frames = guard.as_mut().expect("frame allocator not handed over");
+        let free_before = frames.free_frame_count();
+        let first = frames.allocate_frame();
+        if let Some(frame) = first {
+            unsafe { frames.deallocate_frame(frame) };
+        }
+        let again = frames.allocate_frame();
+        if let Some(frame) = again {
+            unsafe { frames.deallocate_frame(frame) };
+        }
+        let run = frames.allocate_contiguous(16, 16);
+        let free_during = frames.free_frame_count();
+        if let Some(start) = run {
+            unsafe { frames.deallocate_contiguous(start, 16) };
+        }
+        (first, again, free_before, free_during, frames.free_frame_count(), run)
+    };
+
+    assert!(first.is_some());
+    assert_eq!(first, again); // the lowest free frame comes back first
+    let run = run.expect("no 16 free frames in a row");
+    assert_eq!(run.start_address().as_u64() % (16 * 4096), 0);
+    assert_eq!(free_during, free_before - 16);
+    assert_eq!(free_after, free_before);
+}
+kernel_test!(frame_reuse, TestAttributes::new().tags(&["memory"]));
+
+// a private arena from the kernel heap for the allocator tests, given back when dropped
+struct Arena {
+    start: usize,
+}
+
+impl Arena {
+    const SIZE: usize = 16 * 1024;
+
+    fn new() -> Self {
+        let start = unsafe { alloc::alloc::alloc(Self::layout()) } as usize;
+        assert_ne!(start, 0);
+        Arena { start }
+    }
+
+    fn layout() -> core::alloc::Layout {
+        core::alloc::Layout::from_size_align(Self::SIZE, 4096).unwrap()
+    }
+}
+
+impl Drop for Arena {
+    fn drop(&mut self) {
+        unsafe { alloc::alloc::dealloc(self.start as *mut u8, Self::layout()) };
+    }
+}
+
+fn buddy_merging() {
+    use core::alloc::Layout;
+    use kernel::allocator::buddy::BuddyAllocator;
+
+    let arena = Arena::new();
+    let start = arena.start;
+    let mut buddy = BuddyAllocator::new();
+    unsafe { buddy.init(start, Arena::SIZE) };
+
+    let small = Layout::from_size_align(24, 8).unwrap(); // rounded up to a 32 byte block
+    let aligned = Layout::from_size_align(100, 256).unwrap();
+    let a = buddy.allocate(small).unwrap();
+    let b = buddy.allocate(aligned).unwrap();
+    assert_eq!(b.as_ptr() as usize % 256, 0);
+    assert_eq!(buddy.used(), 32 + 256);
+
+    // the whole arena can't be handed out while anything is in use...
+    let whole = Layout::from_size_align(Arena::SIZE, 8).unwrap();
+    assert!(buddy.allocate(whole).is_none());
+    unsafe {
+        buddy.deallocate(a, small);
+        buddy.deallocate(b, aligned);
+    }
+    // ...but once everything is freed the buddies have merged back into one block
+    assert_eq!(buddy.used(), 0);
+    assert_eq!(buddy.allocate(whole).map(|block| block.as_ptr() as usize), Some(start));
+}
+kernel_test!(buddy_merging, TestAttributes::new().tags(&["heap"]));
+
+fn linked_list_coalescing() {
+    use alloc::alloc::GlobalAlloc;
+    use core::alloc::Layout;
+    use kernel::allocator::{linked_list::LinkedListAllocator, Locked};
+
+    let arena = Arena::new();
+    let start = arena.start;
+    let heap = Locked::new(LinkedListAllocator::new());
+    unsafe { heap.lock().init(start, Arena::SIZE) };
+
+    let block = Layout::from_size_align(64, 8).unwrap();
+    let (a, b, c) = unsafe { (heap.alloc(block), heap.alloc(block), heap.alloc(block)) };
+    assert_eq!(b as usize, a as usize + 64);
+    unsafe {
+        // freed out of order, the neighbours still merge into one region
+        heap.dealloc(b, block);
+        heap.dealloc(a, block);
+    }
+    assert_eq!(heap.lock().free(), Arena::SIZE - 64);
+
+    // c can grow in place into the free space behind it, a can't: c is in the way
+    let a = unsafe { heap.alloc(block) };
+    let allocations = heap.lock().stats().total_allocations;
+    let c = unsafe { heap.realloc(c, block, 1024) };
+    assert_eq!(c as usize, start + 128);
+    assert_eq!(heap.lock().stats().total_allocations, allocations); // a resize, not another allocation
+    assert_eq!(heap.lock().stats().bytes_allocated, 64 + 1024);
+    let moved = unsafe { heap.realloc(a, block, 256) };
+    assert_ne!(moved, a);
+    unsafe {
+        // shrinking gives the tail back
+        let c = heap.realloc(c, Layout::from_size_align(1024, 8).unwrap(), 64);
+        assert_eq!(c as usize, start + 128);
+        heap.dealloc(c, block);
+        heap.dealloc(moved, Layout::from_size_align(256, 8).unwrap());
+    }
+    assert_eq!(heap.lock().largest_free_region(), Arena::SIZE);
+}
+kernel_test!(linked_list_coalescing, TestAttributes::new().tags(&["heap"]));
+
+fn private_heap_stays_put() {
+    use alloc::alloc::GlobalAlloc;
+    use core::alloc::Layout;
+    use kernel::allocator::{self, buddy::BuddyAllocator, large, Locked};
+
+    let arena = Arena::new();
+    let heap = Locked::new(BuddyAllocator::new());
+    unsafe { heap.lock().init(arena.start, Arena::SIZE) };
+
+    // only the global allocator grows its heap or gives large allocations pages of their own
+    let (heap_size, mapped) = (allocator::heap_size(), large::mapped_bytes());
+    let small = Layout::from_size_align(64, 8).unwrap();
+    let a = unsafe { heap.alloc(small) };
+    assert!(!a.is_null());
+    assert!(unsafe { heap.alloc(Layout::from_size_align(Arena::SIZE, 8).unwrap()) }.is_null());
+    assert!(unsafe { heap.alloc(Layout::from_size_align(large::LARGE_ALLOCATION, 8).unwrap()) }.is_null());
+    assert_eq!((allocator::heap_size(), large::mapped_bytes()), (heap_size, mapped));
+    unsafe { heap.dealloc(a, small) };
+    assert_eq!(heap.lock().used(), 0);
+}
+kernel_test!(private_heap_stays_put, TestAttributes::new().tags(&["heap"]));
+
+// a debug allocator on its own arena, dropped before the arena it allocates from
+struct DebugHeap {
+    heap: kernel::allocator::debug::DebugAllocator<kernel::allocator::Locked<kernel::allocator::buddy::BuddyAllocator>>,
+    _arena: Arena,
+}
+
+impl DebugHeap {
+    fn new() -> Self {
+        use kernel::allocator::{buddy::BuddyAllocator, debug::DebugAllocator, Locked};
+
+        let arena = Arena::new();
+        let heap = DebugAllocator::new(Locked::new(BuddyAllocator::new()));
+        unsafe { heap.inner().lock().init(arena.start, Arena::SIZE) };
+        DebugHeap { heap, _arena: arena }
+    }
+
+    // runs `misuse`, which should panic, and panics with its message once the arena is given back
+    // (a panic jumps straight out of the test, past any drop)
+    fn expect_panic(self, misuse: impl FnOnce(&Self)) {
+        let result = kernel::recovery::catch_panic(|| misuse(&self));
+        drop(self);
+        if let Err(message) = result {
+            panic!("{}", message);
+        }
+    }
+}
+
+fn debug_allocator_tracking() {
+    use alloc::alloc::GlobalAlloc;
+    use core::alloc::Layout;
+    use kernel::allocator::debug::{ALLOC_FILL, FREE_FILL};
+
+    let debug_heap = DebugHeap::new();
+    let heap = &debug_heap.heap;
+    let layout = Layout::from_size_align(40, 8).unwrap();
+    let (a, b) = unsafe { (heap.alloc(layout), heap.alloc(layout)) };
+    assert!((0..40).all(|offset| unsafe { *a.add(offset) } == ALLOC_FILL));
+
+    let live = heap.live_allocations();
+    assert_eq!(live.iter().map(|allocation| allocation.address).collect::<Vec<_>>(), [a as usize, b as usize]);
+    assert!(live.iter().all(|allocation| allocation.size == 40));
+    if cfg!(frame_pointers) {
+        assert!(live.iter().all(|allocation| allocation.callers[0] != 0));
+    }
+    assert!(live[0].sequence < live[1].sequence);
+
+    unsafe {
+        a.write_bytes(1, 40);
+        heap.dealloc(a, layout);
+        // the buddy allocator only links the block through its first word, in the header
+        assert_eq!(*a.add(39), FREE_FILL);
+        heap.dealloc(b, layout);
+    }
+    assert!(heap.live_allocations().is_empty());
+}
+kernel_test!(debug_allocator_tracking, TestAttributes::new().tags(&["heap"]));
+
+fn debug_allocator_overflow() {
+    use alloc::alloc::GlobalAlloc;
+    use core::alloc::Layout;
+
+    let layout = Layout::from_size_align(40, 8).unwrap();
+    DebugHeap::new().expect_panic(|debug_heap| unsafe {
+        let block = debug_heap.heap.alloc(layout);
+        block.add(40).write(0); // one past the end
+        debug_heap.heap.dealloc(block, layout);
+    });
+}
+kernel_test!(debug_allocator_overflow, TestAttributes::new().tags(&["heap"]).should_panic("red zone after"));
+
+fn debug_allocator_double_free() {
+    use alloc::alloc::GlobalAlloc;
+    use core::alloc::Layout;
+
+    let layout = Layout::from_size_align(40, 8).unwrap();
+    DebugHeap::new().expect_panic(|debug_heap| unsafe {
+        let block = debug_heap.heap.alloc(layout);
+        debug_heap.heap.dealloc(block, layout);
+        debug_heap.heap.dealloc(block, layout);
+    });
+}
+kernel_test!(debug_allocator_double_free, TestAttributes::new().tags(&["heap"]).should_panic("double free"));
+
+fn slab_cache() {
+    use kernel::allocator::slab::{SlabBox, SlabCache};
+
+    struct Node {
+        marker: u64,
+        value: u64,
+    }
+
+    fn construct(object: *mut u8) {
+        unsafe { (object as *mut Node).write(Node { marker: 0xc0ffee, value: 0 }) };
+    }
+
+    static NODES: SlabCache = SlabCache::of::<Node>("test_node").constructor(construct);
+
+    // raw objects come constructed, more than fit into one slab
+    let objects: Vec<_> = (0..300).map(|_| NODES.alloc().expect("out of frames")).collect();
+    assert!(objects.iter().all(|object| unsafe { (*(object.as_ptr() as *const Node)).marker } == 0xc0ffee));
+    let stats = NODES.stats();
+    assert_eq!(stats.active_objects, 300);
+    assert!(stats.slabs >= 2 && stats.slabs * stats.objects_per_slab >= 300);
+
+    for object in objects {
+        unsafe { NODES.free(object) };
+    }
+    let stats = NODES.stats();
+    assert_eq!(stats.active_objects, 0);
+    assert_eq!((stats.slabs, stats.empty_slabs), (1, 1)); // one empty slab is kept around
+
+    let mut node = SlabBox::new_in(Node { marker: 1, value: 2 }, &NODES).unwrap();
+    node.value += 1;
+    assert_eq!((node.marker, node.value), (1, 3));
+    drop(node);
+    assert_eq!(NODES.reclaim(), 1);
+    assert_eq!(NODES.stats().slabs, 0);
+
+    // the running executor keeps its tasks in a cache of its own
+    assert!(kernel::allocator::slab::caches().iter().any(|cache| cache.name() == "task" && cache.stats().active_objects > 0));
+}
+kernel_test!(slab_cache, TestAttributes::new().tags(&["memory"]));
 
 fn println_simple() {
     println!("test_println_simple output");
 }
+kernel_test!("simple_println", println_simple, TestAttributes::new().tags(&["vga"]));
 
 fn println_many() {
     for _ in 0..200 {
         println!("test_println_many output");
     }
 }
+kernel_test!("many_println", println_many, TestAttributes::new().tags(&["vga"]));
+
+fn cp437_mapping() {
+    use kernel::vga_buffer::cp437;
+
+    assert_eq!(cp437::from_char('A'), Some(b'A'));
+    assert_eq!(cp437::from_char('é'), Some(0x82));
+    assert_eq!(cp437::from_char('╔'), Some(0xc9));
+    assert_eq!(cp437::from_char('☺'), Some(0x01));
+    assert_eq!(cp437::from_char('\t'), None);
+    assert_eq!(cp437::to_glyph('語'), cp437::FALLBACK_GLYPH);
+    println!("cp437: é ü ñ ░▒▓ ╔═╗ ½ ° ±");
+}
+kernel_test!(cp437_mapping, TestAttributes::new().tags(&["vga"]));
+
+fn editor_editing() {
+    use kernel::{commands::edit::Editor, fs};
+    use pc_keyboard::{DecodedKey, KeyCode};
+
+    let mut editor = Editor::new("editor_test", "ab\ncd");
+    editor.handle_key(DecodedKey::RawKey(KeyCode::End));
+    editor.handle_key(DecodedKey::Unicode('x'));
+    editor.handle_key(DecodedKey::Unicode('\n'));
+    assert_eq!(editor.text(), "abx\n\ncd");
+    editor.handle_key(DecodedKey::Unicode('\x08'));
+    assert_eq!(editor.cursor(), (0, 3));
+    editor.handle_key(DecodedKey::RawKey(KeyCode::ArrowDown));
+    editor.handle_key(DecodedKey::RawKey(KeyCode::Home));
+    editor.handle_key(DecodedKey::RawKey(KeyCode::Delete));
+    assert_eq!(editor.text(), "abx\nd");
+    assert!(editor.is_modified());
+
+    editor.handle_key(DecodedKey::Unicode('\x13')); // Ctrl+S
+    assert!(!editor.is_modified());
+    assert_eq!(fs::read("editor_test").unwrap(), b"abx\nd");
+    fs::remove("editor_test").unwrap();
+
+    // tabs are saved as they were loaded, only the screen shows them expanded
+    let mut editor = Editor::new("editor_test", "\tx");
+    editor.handle_key(DecodedKey::RawKey(KeyCode::End));
+    editor.handle_key(DecodedKey::Unicode('\t'));
+    assert_eq!(editor.text(), "\tx\t");
+}
+kernel_test!(editor_editing);
+
+fn panic_recovery() {
+    use kernel::recovery::catch_panic;
+
+    assert_eq!(catch_panic(|| {}), Ok(()));
+    let message = catch_panic(|| panic!("expected panic {}", 42)).unwrap_err();
+    assert!(message.contains("expected panic 42"));
+
+    // nested: the inner catch gets the panic, the outer one sees a normal return
+    let outer = catch_panic(|| {
+        assert!(catch_panic(|| assert_eq!(1, 2)).is_err());
+    });
+    assert_eq!(outer, Ok(()));
+}
+kernel_test!(panic_recovery);
+
+// never returns on its own, the timeout has to stop it
+fn spin_forever() {
+    loop {
+        core::hint::spin_loop();
+        kernel::test_registry::checkpoint();
+    }
+}
+kernel_test!("timeout_abort", spin_forever, TestAttributes::new().should_panic("timed out").timeout(10));
+
+fn glob_matching() {
+    use kernel::test_registry::glob_match;
+
+    assert!(glob_match("*", "large_vec"));
+    assert!(glob_match("many_*", "many_boxes_long_lived"));
+    assert!(glob_match("*_vec", "large_vec"));
+    assert!(glob_match("simple_?lloc", "simple_alloc"));
+    assert!(glob_match("m*y*s", "many_boxes"));
+    assert!(!glob_match("many_*", "large_vec"));
+    assert!(!glob_match("simple", "simple_alloc"));
+}
+kernel_test!(glob_matching);
+
+fn log_ring_buffer() {
+    use kernel::log::{Level, LogBuffer, Record};
+
+    // a small buffer of its own, so the kernel log keeps the boot messages
+    const CAPACITY: usize = 8;
+    let mut buffer = LogBuffer::<CAPACITY>::new();
+    for i in 0..CAPACITY + 5 {
+        buffer.push(Record::new(Level::Debug, i as u64, format_args!("message {}", i)));
+    }
+    buffer.push(Record::new(Level::Warn, 13, format_args!("last")));
+
+    let records = buffer.records(Level::Trace);
+    assert_eq!(records.len(), CAPACITY);
+    assert_eq!(records[0].message(), "message 6"); // the oldest ones were overwritten
+    assert_eq!(records[CAPACITY - 1].message(), "last");
+    assert_eq!(buffer.records(Level::Warn).len(), 1);
+    buffer.clear();
+    assert!(buffer.records(Level::Trace).is_empty());
+}
+kernel_test!(log_ring_buffer);
+
+async fn timer_sleep() {
+    use kernel::{task::timer, time};
+
+    let start = time::ticks();
+    timer::sleep(5).await;
+    assert!(time::ticks() - start >= 5);
+}
+kernel_test!(async timer_sleep, TestAttributes::new().tags(&["async"]));
+
+async fn executor_spawn() {
+    use core::sync::atomic::{AtomicBool, Ordering};
+    use kernel::task::{executor, timer};
+
+    static RAN: AtomicBool = AtomicBool::new(false);
+    RAN.store(false, Ordering::SeqCst);
+    executor::spawn(Task::new(async {
+        RAN.store(true, Ordering::SeqCst);
+    }));
+    while !RAN.load(Ordering::SeqCst) {
+        timer::sleep(1).await;
+    }
+}
+kernel_test!(async executor_spawn, TestAttributes::new().tags(&["async"]).timeout(100));
+
+async fn async_panic() {
+    kernel::task::timer::sleep(1).await;
+    panic!("async boom");
+}
+kernel_test!(async async_panic, TestAttributes::new().tags(&["async"]).should_panic("async boom"));
+
+async fn async_timeout() {
+    core::future::pending::<()>().await;
+}
+kernel_test!(async async_timeout, TestAttributes::new().tags(&["async"]).should_panic("timed out").timeout(5));
+
+fn bench_statistics() {
+    use bench_registry::Statistics;
+
+    let mut samples = [9, 1, 5, 3, 7];
+    let stats = Statistics::from_samples(&mut samples).unwrap();
+    assert_eq!((stats.min, stats.mean, stats.median), (1, 5, 5));
+    assert_eq!(stats.stddev, 2); // sqrt(8), rounded down
+    assert_eq!(Statistics::from_samples(&mut [4, 2]).unwrap().median, 3);
+    assert!(Statistics::from_samples(&mut []).is_none());
+    // a difference this large squares past u64::MAX
+    let stats = Statistics::from_samples(&mut [0, 300_000_000_000]).unwrap();
+    assert_eq!(stats.stddev, 150_000_000_000);
+}
+kernel_test!(bench_statistics);
+
+// benchmarks, run with `bench <name>` or `bench all`
+
+fn box_churn() {
+    let boxed = Box::new(core::hint::black_box(42u64));
+    drop(core::hint::black_box(boxed));
+}
+kernel_bench!(box_churn);
+
+fn vec_growth() {
+    let mut vec = Vec::new();
+    for i in 0..256u64 {
+        vec.push(core::hint::black_box(i));
+    }
+    core::hint::black_box(vec);
+}
+kernel_bench!(vec_growth);
 
+// the same object as box_churn, from a slab cache instead of the heap
+fn slab_churn() {
+    use kernel::allocator::slab::{SlabBox, SlabCache};
+
+    static CACHE: SlabCache = SlabCache::of::<u64>("bench_u64");
+    let object = SlabBox::new_in(core::hint::black_box(42u64), &CACHE);
+    drop(core::hint::black_box(object));
+}
+kernel_bench!(slab_churn);
+
+// spawns a few empty tasks on a fresh executor and polls them to completion
+fn task_spawn_poll() {
+    // local: the kernel's executor has to keep the tasks queued with executor::spawn
+    let mut executor = Executor::new_local();
+    for _ in 0..10 {
+        executor.spawn(Task::new(async {}));
+    }
+    executor.run_until_idle();
+}
+kernel_bench!(task_spawn_poll, 100);
 
 // async fn async_number() -> u32 {
 //     42
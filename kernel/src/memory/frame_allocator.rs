@@ -0,0 +1,214 @@
+use super::phys_to_virt;
+use crate::allocator::align_up;
+use bootloader::bootinfo::{MemoryMap, MemoryRegionType};
+use core::slice;
+use x86_64::{
+    structures::paging::{FrameAllocator, FrameDeallocator, PhysFrame, Size4KiB},
+    PhysAddr,
+};
+
+// Physical memory manager: one bit per frame (set = free), plus summary levels on top where
+// a bit is set if the word below it has a free bit. Finding a free frame walks down from the
+// single top word, one word per level, and freeing or taking one updates at most one word
+// per level. The bitmap lives in the first usable region that can hold it.
+
+const FRAME_SIZE: u64 = 4096;
+const MAX_LEVELS: usize = 6; // 64^6 frames, way more than any machine has
+
+/// A FrameAllocator that hands out and takes back the usable frames of the bootloader's memory map.
+pub struct BitmapFrameAllocator {
+    words: &'static mut [u64], // all levels, frames first
+    levels: [usize; MAX_LEVELS], // where each level starts in `words`
+    level_count: usize,
+    frames: usize, // frames level 0 covers, up to the end of the highest usable region
+    usable: usize,
+    free: usize,
+}
+
+impl BitmapFrameAllocator {
+    /// Create a FrameAllocator from the passed memory map.
+    ///
+    /// This function is unsafe because the caller must guarantee that the passed
+    /// memory map is valid and that `memory::init` was called before, since the
+    /// bitmap is written through the physical memory mapping. The main requirement
+    /// is that all frames that are marked as `USABLE` in it are really unused.
+    pub unsafe fn init(memory_map: &'static MemoryMap) -> Self {
+        let usable_regions = || memory_map.iter().filter(|r| r.region_type == MemoryRegionType::Usable);
+        let frames = usable_regions().map(|r| r.range.end_frame_number as usize).max().unwrap_or(0);
+
+        // every level has a bit per word of the level below, up to a level of a single word
+        let mut levels = [0; MAX_LEVELS];
+        let mut level_count = 0;
+        let mut total_words = 0;
+        let mut bits = frames.max(1);
+        loop {
+            assert!(level_count < MAX_LEVELS, "too much physical memory for the frame bitmap");
+            let words = bits.div_ceil(64);
+            levels[level_count] = total_words;
+            level_count += 1;
+            total_words += words;
+            if words == 1 {
+                break;
+            }
+            bits = words;
+        }
+
+        // the bitmap takes the first frames of the first usable region that is big enough
+        let bitmap_frames = (total_words as u64 * 8).div_ceil(FRAME_SIZE);
+        let region = usable_regions()
+            .find(|r| r.range.end_frame_number - r.range.start_frame_number >= bitmap_frames)
+            .expect("no usable region can hold the frame bitmap");
+        let bitmap = phys_to_virt(PhysAddr::new(region.range.start_addr()));
+        let words = slice::from_raw_parts_mut(bitmap.as_mut_ptr::<u64>(), total_words);
+        words.fill(0); // all used, until the memory map says otherwise
+
+        let mut allocator = BitmapFrameAllocator {
+            words,
+            levels,
+            level_count,
+            frames,
+            usable: 0,
+            free: 0,
+        };
+        for region in usable_regions() {
+            for frame in region.range.start_frame_number..region.range.end_frame_number {
+                allocator.set_free(frame as usize);
+                allocator.usable += 1;
+            }
+        }
+        let bitmap_start = region.range.start_frame_number as usize;
+        for frame in bitmap_start..bitmap_start + bitmap_frames as usize {
+            allocator.set_used(frame);
+        }
+        allocator
+    }
+
+    /// Returns how many frames the memory map marks as usable.
+    pub fn usable_frame_count(&self) -> usize {
+        self.usable
+    }
+
+    /// Returns how many frames are handed out right now (including the ones holding the bitmap).
+    pub fn allocated_frame_count(&self) -> usize {
+        self.usable - self.free
+    }
+
+    /// Returns how many frames can still be allocated.
+    pub fn free_frame_count(&self) -> usize {
+        self.free
+    }
+
+    /// Allocates `count` frames in a row, the first one aligned to `align` frames (a power of two).
+    /// Takes time linear in the number of frames, meant for the occasional DMA buffer or huge page.
+    pub fn allocate_contiguous(&mut self, count: usize, align: usize) -> Option<PhysFrame> {
+        if count == 0 {
+            return None;
+        }
+
+        let mut start = 0;
+        while start + count <= self.frames {
+            if self.words[start / 64] == 0 {
+                // no free frame in this word
+                start = align_up((start / 64 + 1) * 64, align);
+                continue;
+            }
+            match (start..start + count).find(|&frame| !self.is_free(frame)) {
+                Some(used) => start = align_up(used + 1, align),
+                None => {
+                    for frame in start..start + count {
+                        self.set_used(frame);
+                    }
+                    return Some(frame_at(start));
+                }
+            }
+        }
+        None
+    }
+
+    /// Gives back `count` frames from `allocate_contiguous`.
+    ///
+    /// Unsafe because the caller must guarantee that none of the frames is still in use.
+    pub unsafe fn deallocate_contiguous(&mut self, start: PhysFrame, count: usize) {
+        let first = frame_index(start);
+        for frame in first..first + count {
+            self.deallocate_frame(frame_at(frame));
+        }
+    }
+
+    fn is_free(&self, frame: usize) -> bool {
+        self.words[frame / 64] >> (frame % 64) & 1 == 1
+    }
+
+    // marks a frame free, and its word as having a free frame in the levels above
+    fn set_free(&mut self, frame: usize) {
+        let mut index = frame;
+        for level in 0..self.level_count {
+            let offset = self.levels[level];
+            let word = &mut self.words[offset + index / 64];
+            let had_free = *word != 0;
+            *word |= 1 << (index % 64);
+            if had_free {
+                break; // the levels above already know
+            }
+            index /= 64;
+        }
+        self.free += 1;
+    }
+
+    // marks a frame used, and its word as full in the levels above if it was the last free one
+    fn set_used(&mut self, frame: usize) {
+        let mut index = frame;
+        for level in 0..self.level_count {
+            let offset = self.levels[level];
+            let word = &mut self.words[offset + index / 64];
+            *word &= !(1 << (index % 64));
+            if *word != 0 {
+                break;
+            }
+            index /= 64;
+        }
+        self.free -= 1;
+    }
+
+    // the lowest free frame
+    fn find_free(&self) -> Option<usize> {
+        let mut index = 0;
+        for level in (0..self.level_count).rev() {
+            let word = self.words[self.levels[level] + index];
+            if word == 0 {
+                return None;
+            }
+            index = index * 64 + word.trailing_zeros() as usize;
+        }
+        Some(index)
+    }
+}
+
+unsafe impl FrameAllocator<Size4KiB> for BitmapFrameAllocator {
+    fn allocate_frame(&mut self) -> Option<PhysFrame> {
+        let frame = self.find_free()?;
+        self.set_used(frame);
+        Some(frame_at(frame))
+    }
+}
+
+impl FrameDeallocator<Size4KiB> for BitmapFrameAllocator {
+    /// Takes back a frame from `allocate_frame` that isn't mapped anywhere any more.
+    unsafe fn deallocate_frame(&mut self, frame: PhysFrame) {
+        let index = frame_index(frame);
+        assert!(
+            index < self.frames && !self.is_free(index),
+            "frame {:#x} freed twice",
+            frame.start_address().as_u64()
+        );
+        self.set_free(index);
+    }
+}
+
+fn frame_at(index: usize) -> PhysFrame {
+    PhysFrame::containing_address(PhysAddr::new(index as u64 * FRAME_SIZE))
+}
+
+fn frame_index(frame: PhysFrame) -> usize {
+    (frame.start_address().as_u64() / FRAME_SIZE) as usize
+}
@@ -0,0 +1,22 @@
+use x86_64::instructions::port::Port;
+
+// QEMU's isa-debug-exit device (see test-args in Cargo.toml) turns a write to its port
+// into an exit with status (value << 1) | 1, so Success exits with 33.
+
+const ISA_DEBUG_EXIT_PORT: u16 = 0xf4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u32)]
+pub enum QemuExitCode {
+    Success = 0x10,
+    Failed = 0x11,
+}
+
+/// Exits QEMU with the given code. Without the isa-debug-exit device it just halts.
+pub fn exit(exit_code: QemuExitCode) -> ! {
+    unsafe {
+        let mut port = Port::new(ISA_DEBUG_EXIT_PORT);
+        port.write(exit_code as u32);
+    }
+    crate::hlt_loop()
+}
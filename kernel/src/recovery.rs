@@ -0,0 +1,154 @@
+use alloc::string::String;
+use core::arch::global_asm;
+use core::fmt::{self, Write};
+use core::panic::PanicInfo;
+use core::ptr;
+use core::sync::atomic::{AtomicPtr, Ordering};
+use spin::Mutex;
+use x86_64::instructions::interrupts;
+
+// Panic recovery without unwinding: `catch_panic` saves the callee-saved registers and the
+// stack pointer (like setjmp), and the panic handler jumps back there (like longjmp).
+// Destructors of the abandoned frames don't run, so whatever the panicking code owned
+// (heap memory, held locks) is leaked. Good enough for running tests from the shell.
+
+const MESSAGE_LENGTH: usize = 256;
+
+/// Registers `try_call` saves so `jump_back` can return from it a second time.
+#[repr(C)]
+struct JumpBuffer {
+    rbx: u64,
+    rbp: u64,
+    r12: u64,
+    r13: u64,
+    r14: u64,
+    r15: u64,
+    rsp: u64, // stack pointer after `try_call` returns
+    rip: u64, // return address of `try_call`
+}
+
+extern "C" {
+    // Calls `function(data)` and returns 0, or 1 if `jump_back` was called with the same buffer.
+    fn try_call(buffer: *mut JumpBuffer, function: extern "C" fn(*mut u8), data: *mut u8) -> u64;
+    fn jump_back(buffer: *const JumpBuffer) -> !;
+}
+
+global_asm!(
+    ".global try_call",
+    "try_call:",
+    "mov [rdi], rbx",
+    "mov [rdi + 8], rbp",
+    "mov [rdi + 16], r12",
+    "mov [rdi + 24], r13",
+    "mov [rdi + 32], r14",
+    "mov [rdi + 40], r15",
+    "lea rax, [rsp + 8]",
+    "mov [rdi + 48], rax",
+    "mov rax, [rsp]",
+    "mov [rdi + 56], rax",
+    "sub rsp, 8", // keep the stack 16 byte aligned for the call
+    "mov rdi, rdx",
+    "call rsi",
+    "add rsp, 8",
+    "xor eax, eax",
+    "ret",
+    "",
+    ".global jump_back",
+    "jump_back:",
+    "mov rbx, [rdi]",
+    "mov rbp, [rdi + 8]",
+    "mov r12, [rdi + 16]",
+    "mov r13, [rdi + 24]",
+    "mov r14, [rdi + 32]",
+    "mov r15, [rdi + 40]",
+    "mov rsp, [rdi + 48]",
+    "mov eax, 1",
+    "jmp qword ptr [rdi + 56]",
+);
+
+// the innermost active catch_panic, null if panics aren't caught right now
+static GUARD: AtomicPtr<JumpBuffer> = AtomicPtr::new(ptr::null_mut());
+
+// the panic message, written by the panic handler, which shouldn't allocate
+struct Message {
+    text: [u8; MESSAGE_LENGTH],
+    length: usize,
+}
+
+impl Write for Message {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for character in s.chars() {
+            let length = character.len_utf8();
+            if self.length + length > MESSAGE_LENGTH {
+                break;
+            }
+            character.encode_utf8(&mut self.text[self.length..]);
+            self.length += length;
+        }
+        Ok(())
+    }
+}
+
+static MESSAGE: Mutex<Message> = Mutex::new(Message {
+    text: [0; MESSAGE_LENGTH],
+    length: 0,
+});
+
+/// Runs `function` and returns its result, or the panic message if it panicked.
+pub fn catch_panic<R, F: FnOnce() -> R>(function: F) -> Result<R, String> {
+    // what `call` gets through the data pointer: the function going in, its result coming out
+    struct Slot<R, F> {
+        function: Option<F>,
+        result: Option<R>,
+    }
+
+    extern "C" fn call<R, F: FnOnce() -> R>(data: *mut u8) {
+        let slot = unsafe { &mut *(data as *mut Slot<R, F>) };
+        if let Some(function) = slot.function.take() {
+            slot.result = Some(function());
+        }
+    }
+
+    let mut slot = Slot { function: Some(function), result: None };
+    let mut buffer = JumpBuffer { rbx: 0, rbp: 0, r12: 0, r13: 0, r14: 0, r15: 0, rsp: 0, rip: 0 };
+    let interrupts_enabled = interrupts::are_enabled();
+    let outer = GUARD.swap(&mut buffer, Ordering::SeqCst);
+
+    let panicked = unsafe { try_call(&mut buffer, call::<R, F>, &mut slot as *mut Slot<R, F> as *mut u8) };
+
+    GUARD.store(outer, Ordering::SeqCst);
+    if panicked == 0 {
+        if let Some(result) = slot.result {
+            return Ok(result);
+        }
+    }
+
+    // the panic may have happened inside `without_interrupts`
+    if interrupts_enabled {
+        interrupts::enable();
+    }
+    let message = MESSAGE.lock();
+    Err(String::from(core::str::from_utf8(&message.text[..message.length]).unwrap_or("")))
+}
+
+/// Ends the innermost `catch_panic` as if its function had panicked with `message`.
+/// Does nothing if no `catch_panic` is active. Used to stop tests that time out.
+pub fn abort(message: fmt::Arguments) {
+    let guard = GUARD.load(Ordering::SeqCst);
+    if guard.is_null() {
+        return;
+    }
+
+    // try_lock: the panic might come from code that was just writing the message
+    if let Some(mut text) = MESSAGE.try_lock() {
+        text.length = 0;
+        let _ = text.write_fmt(message);
+    }
+    unsafe { jump_back(guard) }
+}
+
+/// Called by the panic handler: if the panic happened inside `catch_panic`, jumps back there.
+/// Returns only if nobody catches it.
+pub fn recover(info: &PanicInfo) {
+    abort(format_args!("{}", info));
+}
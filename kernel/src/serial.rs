@@ -0,0 +1,70 @@
+use lazy_static::lazy_static;
+use spin::Mutex;
+use uart_16550::SerialPort;
+use x86_64::instructions::{interrupts, port::Port};
+
+// COM1. Everything printed to the active console is mirrored here, and bytes received
+// from it are fed to the shell (see task::serial), so the kernel can be used headless.
+
+pub const COM1_BASE: u16 = 0x3F8;
+const COM1_IRQ: u8 = 4;
+const PIC1_DATA_PORT: u16 = 0x21;
+const LINE_STATUS: u16 = 5; // register offset
+const DATA_READY: u8 = 1; // line status bit
+pub(crate) const FIFO_SIZE: usize = 16;
+
+lazy_static! {
+    pub static ref SERIAL1: Mutex<SerialPort> = {
+        let mut serial_port = unsafe { SerialPort::new(COM1_BASE) };
+        serial_port.init(); // also enables the "data received" interrupt
+        Mutex::new(serial_port)
+    };
+}
+
+/// Sets up COM1 and unmasks its IRQ. Has to run after the PICs are initialized.
+pub fn init() {
+    lazy_static::initialize(&SERIAL1);
+
+    let mut mask_port: Port<u8> = Port::new(PIC1_DATA_PORT);
+    unsafe {
+        let mask = mask_port.read();
+        mask_port.write(mask & !(1 << COM1_IRQ));
+    }
+    crate::log_info!("serial console on COM1 ({:#x})", COM1_BASE);
+}
+
+/// Reads the bytes waiting in the receive FIFO into `buffer`, as many as fit, and returns how many.
+pub(crate) fn receive_pending(buffer: &mut [u8]) -> usize {
+    let _port = SERIAL1.lock(); // nobody else touches the UART meanwhile
+    let mut line_status: Port<u8> = Port::new(COM1_BASE + LINE_STATUS);
+    let mut data: Port<u8> = Port::new(COM1_BASE);
+    let mut count = 0;
+    while count < buffer.len() && unsafe { line_status.read() } & DATA_READY != 0 {
+        buffer[count] = unsafe { data.read() };
+        count += 1;
+    }
+    count
+}
+
+#[doc(hidden)]
+pub fn _print(args: ::core::fmt::Arguments) {
+    use core::fmt::Write;
+
+    interrupts::without_interrupts(|| {
+        SERIAL1.lock().write_fmt(args).expect("Printing to serial failed");
+    });
+}
+
+/// Prints to COM1 only.
+#[macro_export]
+macro_rules! serial_print {
+    ($($arg:tt)*) => ($crate::serial::_print(format_args!($($arg)*)));
+}
+
+/// Prints to COM1 only, appending a newline.
+#[macro_export]
+macro_rules! serial_println {
+    () => ($crate::serial_print!("\n"));
+    ($fmt:expr) => ($crate::serial_print!(concat!($fmt, "\n")));
+    ($fmt:expr, $($arg:tt)*) => ($crate::serial_print!(concat!($fmt, "\n"), $($arg)*));
+}
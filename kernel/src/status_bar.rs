@@ -0,0 +1,38 @@
+use crate::{allocator, console, time, commands::bsod::BSOD_ACTIVE, task::{executor, timer}};
+use alloc::format;
+use core::sync::atomic::Ordering;
+use x86_64::instructions::interrupts;
+
+// Status line in the top row of every console: uptime, heap usage, live tasks and the shown console.
+
+/// Redraws the status line on the active console.
+pub fn draw() {
+    if BSOD_ACTIVE.load(Ordering::SeqCst) {
+        return; // don't paint over the panic screen
+    }
+
+    let uptime = time::uptime_seconds();
+    let (heap_used, heap_free) = allocator::heap_usage();
+    let line = format!(
+        " VertexDOS | up {}:{:02}:{:02} | heap {} B used, {} B free | tasks {} | tty{}",
+        uptime / 3600,
+        uptime / 60 % 60,
+        uptime % 60,
+        heap_used,
+        heap_free,
+        executor::live_tasks(),
+        console::active() + 1,
+    );
+
+    interrupts::without_interrupts(|| {
+        console::active_writer().lock().write_status_line(&line);
+    });
+}
+
+/// Periodic task that refreshes the status line once a second.
+pub async fn run() {
+    loop {
+        draw();
+        timer::sleep(time::TIMER_HZ).await;
+    }
+}
@@ -1,7 +1,34 @@
 use super::{Task, TaskId};
-use alloc::{collections::BTreeMap, sync::Arc, task::Wake};
+use crate::allocator::slab::{SlabBox, SlabCache};
+use alloc::{collections::{BTreeMap, VecDeque}, sync::Arc, task::Wake};
 use core::task::{Waker, Context, Poll};
+use core::sync::atomic::{AtomicUsize, Ordering};
 use crossbeam_queue::ArrayQueue;
+use spin::Mutex;
+
+static LIVE_TASKS: AtomicUsize = AtomicUsize::new(0); // spawned tasks that haven't finished yet
+
+/// Returns how many spawned tasks are still alive.
+pub fn live_tasks() -> usize {
+    LIVE_TASKS.load(Ordering::Relaxed)
+}
+
+// Tasks spawned from inside other tasks, picked up by the executor on its next round.
+// Task futures aren't Send, which is fine: there is one core and the queue is only used
+// by the executor and the tasks it polls, never by interrupt handlers.
+struct SpawnQueue(Mutex<VecDeque<Task>>);
+
+unsafe impl Sync for SpawnQueue {}
+
+static SPAWN_QUEUE: SpawnQueue = SpawnQueue(Mutex::new(VecDeque::new()));
+
+// the executors keep their tasks in objects from this cache
+static TASKS: SlabCache = SlabCache::of::<Task>("task");
+
+/// Spawns a task on the running executor, for code that doesn't own the `Executor`.
+pub fn spawn(task: Task) {
+    SPAWN_QUEUE.0.lock().push_back(task);
+}
 
 
 
@@ -34,26 +61,54 @@ impl Wake for TaskWaker {
 }
 
 pub struct Executor {
-    tasks: BTreeMap<TaskId, Task>, // contains the actual task instances
+    tasks: BTreeMap<TaskId, SlabBox<Task>>, // contains the actual task instances
     task_queue: Arc<ArrayQueue<TaskId>>, // Arc implements reference counting (share ownership of the value among multiple owners)
     waker_cache: BTreeMap<TaskId, Waker>,
+    owns_spawn_queue: bool, // runs the tasks from `spawn`
 }
 
 impl Executor {
+    /// The kernel's executor, it also runs the tasks started with `spawn`.
     pub fn new() -> Self {
         Executor { 
             tasks: BTreeMap::new(), 
             task_queue: Arc::new(ArrayQueue::new(100)), 
-            waker_cache: BTreeMap::new() 
+            waker_cache: BTreeMap::new(),
+            owns_spawn_queue: true,
+        }
+    }
+
+    /// An executor that only runs the tasks given to its own `spawn`, for benchmarks and
+    /// tests that run next to the kernel's executor.
+    pub fn new_local() -> Self {
+        Executor {
+            owns_spawn_queue: false,
+            ..Executor::new()
         }
     }
 
     pub fn spawn(&mut self, task: Task) {
         let task_id = task.id;
-        if self.tasks.insert(task.id, task).is_some() {
+        let task = SlabBox::new_in(task, &TASKS).expect("out of memory for tasks");
+        if self.tasks.insert(task_id, task).is_some() {
             panic!("task with same ID already in tasks");
         }
         self.task_queue.push(task_id).expect("queue full");
+        LIVE_TASKS.fetch_add(1, Ordering::Relaxed);
+    }
+
+    // moves the tasks from the global spawn queue into this executor, if it runs them
+    fn spawn_queued(&mut self) {
+        if !self.owns_spawn_queue {
+            return;
+        }
+        loop {
+            let task = SPAWN_QUEUE.0.lock().pop_front();
+            match task {
+                Some(task) => self.spawn(task),
+                None => break,
+            }
+        }
     }
 
     fn run_ready_tasks(&mut self) {
@@ -62,6 +117,7 @@ impl Executor {
             tasks,
             task_queue,
             waker_cache,
+            ..
         } = self;
 
         while let Ok(task_id) = task_queue.pop() {
@@ -78,24 +134,42 @@ impl Executor {
                     // task done -> remove it and its cached waker
                     tasks.remove(&task_id);
                     waker_cache.remove(&task_id);
+                    LIVE_TASKS.fetch_sub(1, Ordering::Relaxed);
                 }
                 Poll::Pending => {}
             }
         }
     }
 
+    /// Polls tasks until none is ready any more, then returns instead of halting.
+    /// Lets tests drive tasks step by step.
+    pub fn run_until_idle(&mut self) {
+        loop {
+            self.spawn_queued();
+            self.run_ready_tasks();
+            if self.is_idle() {
+                break;
+            }
+        }
+    }
+
     pub fn run(&mut self) -> ! {
         loop {
+            self.spawn_queued();
             self.run_ready_tasks();
             self.sleep_if_idle();
         }
     }
 
+    fn is_idle(&self) -> bool {
+        self.task_queue.is_empty() && (!self.owns_spawn_queue || SPAWN_QUEUE.0.lock().is_empty())
+    }
+
     fn sleep_if_idle(&self) {
         use x86_64::instructions::interrupts::{self, enable_and_hlt};
 
         interrupts::disable();
-        if self.task_queue.is_empty() {
+        if self.is_idle() {
             enable_and_hlt();
         } else {
             interrupts::enable();
@@ -1,6 +1,6 @@
-use crate::{print, println, command_registry::run_command, vga_buffer::WRITER, commands::bsod::BSOD_ACTIVE};
+use crate::{print, log_warn, console, status_bar, commands::bsod::BSOD_ACTIVE};
+use alloc::boxed::Box;
 use core::sync::atomic::Ordering;
-use spin::Mutex;
 use conquer_once::spin::OnceCell;
 use core::{
     pin::Pin,
@@ -11,32 +11,128 @@ use futures_util::{
     stream::{Stream, StreamExt},
     task::AtomicWaker,
 };
-use lazy_static::lazy_static;
-use pc_keyboard::{layouts, DecodedKey, HandleControl, Keyboard, ScancodeSet1};
+use pc_keyboard::{layouts, DecodedKey, HandleControl, Keyboard, KeyCode, KeyState, ScancodeSet1};
+use spin::Mutex;
 
 
 static WAKER: AtomicWaker = AtomicWaker::new();
 static SCANCODE_QUEUE: OnceCell<ArrayQueue<u8>> = OnceCell::uninit();
-pub const INPUT_BUFFER_SIZE: usize = 256; // Maximum command length
 
-lazy_static! {
-    static ref INPUT_BUFFER: Mutex<[u8; INPUT_BUFFER_SIZE]> = Mutex::new([0; INPUT_BUFFER_SIZE]);
-    static ref INPUT_BUFFER_POSITION: Mutex<usize> = Mutex::new(0);
+/// Receives decoded keys instead of the shell while it is installed.
+/// Returns `false` once it is done, which hands the keyboard back to the shell.
+pub type KeyHandler = Box<dyn FnMut(DecodedKey) -> bool + Send>;
+
+static KEY_HANDLER: Mutex<Option<KeyHandler>> = Mutex::new(None);
+
+/// Sends all following keys to `handler` (full-screen programs like `gfxdemo` use this).
+/// Alt+F1..F4 doesn't switch consoles until the keyboard is released again.
+pub fn grab(handler: KeyHandler) {
+    *KEY_HANDLER.lock() = Some(handler);
+}
+
+/// Takes the keyboard away from the program that grabbed it and gives it back to the shell.
+/// For programs that finish on their own instead of in response to a key.
+pub fn release() {
+    let handler = KEY_HANDLER.lock().take();
+    if handler.is_some() {
+        console::print_prompt();
+    }
+}
+
+/// Whether a program currently owns the keyboard.
+pub fn is_grabbed() -> bool {
+    KEY_HANDLER.lock().is_some()
+}
+
+// gives the key to the installed handler, returns false if there is none
+fn dispatch_to_handler(key: DecodedKey) -> bool {
+    // take the handler out, so it can call `grab` itself without deadlocking
+    let handler = KEY_HANDLER.lock().take();
+    match handler {
+        Some(mut handler) => {
+            if handler(key) {
+                let mut slot = KEY_HANDLER.lock();
+                if slot.is_none() {
+                    *slot = Some(handler);
+                }
+            } else if !is_grabbed() {
+                console::print_prompt(); // the shell has the keyboard again
+            }
+            true
+        }
+        None => false,
+    }
 }
 
 
 pub(crate) fn add_scancode(scancode: u8) {
     if let Ok(queue) = SCANCODE_QUEUE.try_get() {
         if let Err(_) = queue.push(scancode) {
-            println!("WARNING: scancode queue full; dropping keyboard input");
+            log_warn!("scancode queue full; dropping keyboard input");
         } else {
             WAKER.wake();
         }
     } else {
-        println!("WARNING: scancode queue uninitialized");
+        log_warn!("scancode queue uninitialized");
+    }
+}
+
+/// Queues a scancode as if IRQ 1 had delivered it, for tests that drive the keyboard task.
+pub fn inject_scancode(scancode: u8) {
+    add_scancode(scancode);
+}
+
+const LEFT_SHIFT: u8 = 0x2A;
+const RELEASED: u8 = 0x80; // set 1 break codes are the make code with the top bit set
+
+/// Types `text` by injecting the set 1 make and break codes of every character.
+///
+/// Panics on characters a US keyboard can't type in one key press.
+pub fn inject_text(text: &str) {
+    for character in text.chars() {
+        let (scancode, shift) = scancode_for(character)
+            .unwrap_or_else(|| panic!("no scancode for {:?}", character));
+        if shift {
+            inject_scancode(LEFT_SHIFT);
+        }
+        inject_scancode(scancode);
+        inject_scancode(scancode | RELEASED);
+        if shift {
+            inject_scancode(LEFT_SHIFT | RELEASED);
+        }
     }
 }
 
+// make code of the key that types `character` on a US layout, and whether it needs Shift
+fn scancode_for(character: char) -> Option<(u8, bool)> {
+    const ROWS: [(&str, &str, u8); 4] = [
+        // unshifted keys, shifted keys, make code of the first key
+        ("1234567890-=", "!@#$%^&*()_+", 0x02),
+        ("qwertyuiop[]", "QWERTYUIOP{}", 0x10),
+        ("asdfghjkl;'`", "ASDFGHJKL:\"~", 0x1E),
+        ("zxcvbnm,./", "ZXCVBNM<>?", 0x2C),
+    ];
+
+    match character {
+        '\n' => return Some((0x1C, false)),
+        ' ' => return Some((0x39, false)),
+        '\x08' => return Some((0x0E, false)),
+        '\t' => return Some((0x0F, false)),
+        '\\' => return Some((0x2B, false)),
+        '|' => return Some((0x2B, true)),
+        _ => {}
+    }
+    for (plain, shifted, first) in ROWS {
+        if let Some(index) = plain.chars().position(|c| c == character) {
+            return Some((first + index as u8, false));
+        }
+        if let Some(index) = shifted.chars().position(|c| c == character) {
+            return Some((first + index as u8, true));
+        }
+    }
+    None
+}
+
 pub struct ScancodeStream {
     _private: (),
 }
@@ -75,9 +171,34 @@ impl Stream for ScancodeStream {
     }
 }
 
+// maps F1..F4 to the index of the virtual console they switch to
+fn console_for_key(code: KeyCode) -> Option<usize> {
+    match code {
+        KeyCode::F1 => Some(0),
+        KeyCode::F2 => Some(1),
+        KeyCode::F3 => Some(2),
+        KeyCode::F4 => Some(3),
+        _ => None,
+    }
+}
+
+/// Gives a decoded key to the program that grabbed the keyboard, or to the shell.
+/// Other input sources (like the serial console) use this too.
+pub fn dispatch_key(key: DecodedKey) {
+    if BSOD_ACTIVE.load(Ordering::SeqCst) || dispatch_to_handler(key) {
+        return;
+    }
+
+    match key {
+        DecodedKey::Unicode(character) => console::handle_char(character),
+        DecodedKey::RawKey(key) => print!("{:?}", key),
+    }
+}
+
 pub async fn print_keypress() {
     let mut scancodes = ScancodeStream::new();
-    let mut keyboard = Keyboard::new(layouts::Us104Key, ScancodeSet1, HandleControl::Ignore);
+    let mut keyboard = Keyboard::new(layouts::Us104Key, ScancodeSet1, HandleControl::MapLettersToUnicode);
+    let mut alt_pressed = false;
 
     while let Some(scancode) = scancodes.next().await {
         if  BSOD_ACTIVE.load(Ordering::SeqCst) {
@@ -85,48 +206,24 @@ pub async fn print_keypress() {
         }
 
         if let Ok(Some(key_event)) = keyboard.add_byte(scancode) {
-            if let Some(key) = keyboard.process_keyevent(key_event) {
-                match key {
-                    DecodedKey::Unicode(character) => {
-                        if character == '\n' {
-                            // Enter key pressed
-                            println!(""); // Move to a new line
-                        
-                            // Get the command from the buffer
-                            let mut buffer_lock = INPUT_BUFFER.lock();
-                            let position = *INPUT_BUFFER_POSITION.lock();
-                            let command = core::str::from_utf8(&buffer_lock[..position]).unwrap_or(""); // slice up the position and convert to string
-                        
-                            // Handle the command
-                            run_command(command);
-                        
-                            // Clear the buffer and reset the position
-                            *buffer_lock = [0; INPUT_BUFFER_SIZE];
-                            *INPUT_BUFFER_POSITION.lock() = 0;
-                        
-                            // Print the prompt for the next command
-                            print!("> ");
-                        } else if character == '\x08' {
-                            // Backspace key pressed
-                            let mut writer = WRITER.lock();
-                            writer.remove_previous_symbol();
-                            let mut position = INPUT_BUFFER_POSITION.lock();
-                            if *position > 0 {
-                                *position -= 1;
-                            }
-                        } else {
-                            print!("{}", character);
-                            let mut buffer = INPUT_BUFFER.lock();
-                            let mut position = INPUT_BUFFER_POSITION.lock();
-                            if *position < INPUT_BUFFER_SIZE {
-                                buffer[*position] = character as u8;
-                                *position += 1;
-                            }
-                        }
-                    },
-                    DecodedKey::RawKey(key) => print!("{:?}", key),
+            // pc-keyboard doesn't report Alt, so track it ourselves
+            if key_event.code == KeyCode::AltLeft || key_event.code == KeyCode::AltRight {
+                alt_pressed = key_event.state == KeyState::Down;
+            }
+
+            // a program that grabbed the keyboard keeps its console, its keys must not reach it
+            // from another one
+            if alt_pressed && key_event.state == KeyState::Down && !is_grabbed() {
+                if let Some(index) = console_for_key(key_event.code) {
+                    console::switch_to(index);
+                    status_bar::draw();
+                    continue;
                 }
             }
+
+            if let Some(key) = keyboard.process_keyevent(key_event) {
+                dispatch_key(key);
+            }
         }
     }
     
@@ -3,7 +3,9 @@ use alloc::boxed::Box;
 
 pub mod simple_executor;
 pub mod keyboard;
+pub mod serial;
 pub mod executor;
+pub mod timer;
 
 // a little about multitasking:
 // Multitasking - ability to manage multiple tasks and manage memory among them
@@ -0,0 +1,110 @@
+use crate::{log_warn, task::keyboard};
+use conquer_once::spin::OnceCell;
+use core::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+use crossbeam_queue::ArrayQueue;
+use futures_util::{
+    stream::{Stream, StreamExt},
+    task::AtomicWaker,
+};
+use pc_keyboard::DecodedKey;
+
+// Input from COM1, handled the same way as the keyboard: the interrupt handler only queues
+// the byte, a task decodes it and hands it to whoever owns the keyboard.
+
+static WAKER: AtomicWaker = AtomicWaker::new();
+static BYTE_QUEUE: OnceCell<ArrayQueue<u8>> = OnceCell::uninit();
+
+/// Called by the COM1 interrupt handler.
+pub(crate) fn add_byte(byte: u8) {
+    if let Ok(queue) = BYTE_QUEUE.try_get() {
+        if queue.push(byte).is_err() {
+            log_warn!("serial queue full; dropping input");
+        } else {
+            WAKER.wake();
+        }
+    }
+    // before the task runs there is nobody to give the input to, so it is dropped silently
+}
+
+pub struct ByteStream {
+    _private: (),
+}
+
+impl ByteStream {
+    pub fn new() -> Self {
+        BYTE_QUEUE
+            .try_init_once(|| ArrayQueue::new(100))
+            .expect("ByteStream::new should only be called once");
+        ByteStream { _private: () }
+    }
+}
+
+impl Stream for ByteStream {
+    type Item = u8;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<u8>> {
+        let queue = BYTE_QUEUE.try_get().expect("serial queue not initialized");
+
+        if let Ok(byte) = queue.pop() {
+            return Poll::Ready(Some(byte));
+        }
+
+        WAKER.register(cx.waker());
+        match queue.pop() {
+            Ok(byte) => {
+                WAKER.take();
+                Poll::Ready(Some(byte))
+            }
+            Err(crossbeam_queue::PopError) => Poll::Pending,
+        }
+    }
+}
+
+// Collects the bytes of one UTF-8 encoded character.
+struct Utf8Decoder {
+    buffer: [u8; 4],
+    length: usize,
+}
+
+impl Utf8Decoder {
+    fn push(&mut self, byte: u8) -> Option<char> {
+        self.buffer[self.length] = byte;
+        self.length += 1;
+        match core::str::from_utf8(&self.buffer[..self.length]) {
+            Ok(text) => {
+                self.length = 0;
+                text.chars().next()
+            }
+            Err(err) if err.error_len().is_some() || self.length == self.buffer.len() => {
+                self.length = 0; // invalid sequence, drop it
+                None
+            }
+            Err(_) => None, // wait for the rest of the character
+        }
+    }
+}
+
+/// Feeds characters typed in the host terminal to the shell.
+pub async fn process_input() {
+    let mut bytes = ByteStream::new();
+    let mut decoder = Utf8Decoder { buffer: [0; 4], length: 0 };
+    let mut after_cr = false;
+
+    while let Some(byte) = bytes.next().await {
+        let follows_cr = core::mem::replace(&mut after_cr, byte == b'\r');
+        // terminals send CR (or CRLF) for Enter and DEL for Backspace
+        let character = match byte {
+            b'\r' => '\n',
+            b'\n' if follows_cr => continue, // Enter was already handled with the CR
+            0x7f => '\x08',
+            byte => match decoder.push(byte) {
+                Some(character) => character,
+                None => continue,
+            },
+        };
+        keyboard::dispatch_key(DecodedKey::Unicode(character));
+    }
+}
@@ -0,0 +1,99 @@
+use crate::time;
+use core::{
+    future::Future,
+    pin::Pin,
+    sync::atomic::{AtomicBool, AtomicU64, Ordering},
+    task::{Context, Poll},
+};
+use futures_util::task::AtomicWaker;
+
+// the timer interrupt can't allocate, so sleeping tasks park their wakers in a fixed table
+const MAX_SLEEPERS: usize = 32;
+
+static SLEEPERS: [AtomicWaker; MAX_SLEEPERS] = [const { AtomicWaker::new() }; MAX_SLEEPERS];
+static SLOT_USED: [AtomicBool; MAX_SLEEPERS] = [const { AtomicBool::new(false) }; MAX_SLEEPERS];
+static DEADLINES: [AtomicU64; MAX_SLEEPERS] = [const { AtomicU64::new(u64::MAX) }; MAX_SLEEPERS]; // no deadline
+
+/// Called from the timer interrupt, wakes every sleeper whose deadline has passed.
+pub(crate) fn wake_sleepers() {
+    let now = time::ticks();
+    for slot in 0..MAX_SLEEPERS {
+        if SLOT_USED[slot].load(Ordering::Acquire) && DEADLINES[slot].load(Ordering::Acquire) <= now {
+            SLEEPERS[slot].wake();
+        }
+    }
+}
+
+fn claim_slot(deadline: u64) -> Option<usize> {
+    for slot in 0..MAX_SLEEPERS {
+        if SLOT_USED[slot]
+            .compare_exchange(false, true, Ordering::AcqRel, Ordering::Acquire)
+            .is_ok()
+        {
+            DEADLINES[slot].store(deadline, Ordering::Release);
+            return Some(slot);
+        }
+    }
+    None
+}
+
+fn release_slot(slot: usize) {
+    DEADLINES[slot].store(u64::MAX, Ordering::Release);
+    SLEEPERS[slot].take();
+    SLOT_USED[slot].store(false, Ordering::Release);
+}
+
+/// A future that completes once the given number of timer ticks have passed.
+pub struct Sleep {
+    deadline: u64,
+    slot: Option<usize>,
+}
+
+/// Sleeps for `ticks` timer ticks (see `time::TIMER_HZ`).
+pub fn sleep(ticks: u64) -> Sleep {
+    Sleep {
+        deadline: time::ticks() + ticks,
+        slot: None,
+    }
+}
+
+impl Future for Sleep {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<()> {
+        if time::ticks() >= self.deadline {
+            return Poll::Ready(());
+        }
+
+        let slot = match self.slot {
+            Some(slot) => slot,
+            None => match claim_slot(self.deadline) {
+                Some(slot) => {
+                    self.slot = Some(slot);
+                    slot
+                }
+                None => {
+                    // no free slot, fall back to polling again
+                    cx.waker().wake_by_ref();
+                    return Poll::Pending;
+                }
+            },
+        };
+
+        SLEEPERS[slot].register(cx.waker());
+        // check again, the tick might have happened before the waker was registered
+        if time::ticks() >= self.deadline {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+impl Drop for Sleep {
+    fn drop(&mut self) {
+        if let Some(slot) = self.slot.take() {
+            release_slot(slot);
+        }
+    }
+}
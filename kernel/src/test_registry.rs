@@ -1,29 +1,423 @@
-use alloc::collections::BTreeMap;
+use alloc::{boxed::Box, collections::BTreeMap, format, string::String, vec, vec::Vec};
+use core::future::Future;
+use core::pin::Pin;
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use core::task::{Context, Poll};
 use spin::Mutex;
 use lazy_static::lazy_static;
 
-use crate::println;
+use crate::{print, println, println_colored, error, recovery, time};
+use crate::task::{executor, keyboard, timer, Task};
+use crate::vga_buffer::Color;
 
 // A type alias for test functions
 pub type TestFunction = fn() -> ();
 
+/// How a registered test is run. Built like `TestAttributes::new().should_panic("overflow").tags(&["heap"])`.
+#[derive(Debug, Clone, Copy)]
+pub struct TestAttributes {
+    /// The test passes only if it panics with a message containing this (`""` accepts any panic).
+    pub should_panic: Option<&'static str>,
+    /// Ignored tests are skipped by `test all` and globs, but still run when named directly.
+    pub ignore: bool,
+    /// The test fails if it runs longer than this many timer ticks. Async tests are stopped once
+    /// the time is up. For sync tests it is only checked after they return: a sync test that hangs
+    /// hangs the run, unless it calls `checkpoint`, which stops it there once the time is up.
+    pub timeout: Option<u64>,
+    pub tags: &'static [&'static str],
+}
+
+impl TestAttributes {
+    pub const fn new() -> TestAttributes {
+        TestAttributes {
+            should_panic: None,
+            ignore: false,
+            timeout: None,
+            tags: &[],
+        }
+    }
+
+    pub const fn should_panic(mut self, expected: &'static str) -> TestAttributes {
+        self.should_panic = Some(expected);
+        self
+    }
+
+    pub const fn ignore(mut self) -> TestAttributes {
+        self.ignore = true;
+        self
+    }
+
+    pub const fn timeout(mut self, ticks: u64) -> TestAttributes {
+        self.timeout = Some(ticks);
+        self
+    }
+
+    pub const fn tags(mut self, tags: &'static [&'static str]) -> TestAttributes {
+        self.tags = tags;
+        self
+    }
+}
+
+/// What a test runs: a plain function, or an `async fn` awaited on the executor.
+#[derive(Debug, Clone, Copy)]
+pub enum TestBody {
+    Sync(TestFunction),
+    Async(AsyncTestFunction),
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct RegisteredTest {
+    pub body: TestBody,
+    pub attributes: TestAttributes,
+}
+
 // A global registry of test functions
 lazy_static! {
-    pub static ref TEST_REGISTRY: Mutex<BTreeMap<&'static str, TestFunction>> = Mutex::new(BTreeMap::new());
+    pub static ref TEST_REGISTRY: Mutex<BTreeMap<&'static str, RegisteredTest>> = Mutex::new(BTreeMap::new());
+}
+
+/// A test placed in the `kernel_tests` linker section by `kernel_test!`.
+pub struct TestEntry {
+    pub name: &'static str,
+    pub body: TestBody,
+    pub attributes: TestAttributes,
+}
+
+/// Registers a test at boot. The name defaults to the function name, `async` marks an `async fn`:
+///
+/// `kernel_test!(large_vec, TestAttributes::new().tags(&["heap"]));`
+/// `kernel_test!("equal_test", trivial_assertion);`
+/// `kernel_test!(async timer_sleep);`
+#[macro_export]
+macro_rules! kernel_test {
+    (async $function:ident $(, $attributes:expr)?) => {
+        $crate::kernel_test!(async stringify!($function), $function $(, $attributes)?);
+    };
+    (async $name:expr, $function:path) => {
+        $crate::kernel_test!(async $name, $function, $crate::test_registry::TestAttributes::new());
+    };
+    (async $name:expr, $function:path, $attributes:expr) => {
+        const _: () = {
+            fn start() -> $crate::test_registry::TestFuture {
+                $crate::test_registry::TestFuture::new($function())
+            }
+            $crate::kernel_test!(@entry $name, $crate::test_registry::TestBody::Async(start), $attributes);
+        };
+    };
+    ($function:ident $(, $attributes:expr)?) => {
+        $crate::kernel_test!(stringify!($function), $function $(, $attributes)?);
+    };
+    ($name:expr, $function:path) => {
+        $crate::kernel_test!($name, $function, $crate::test_registry::TestAttributes::new());
+    };
+    ($name:expr, $function:path, $attributes:expr) => {
+        $crate::kernel_test!(@entry $name, $crate::test_registry::TestBody::Sync($function), $attributes);
+    };
+    (@entry $name:expr, $body:expr, $attributes:expr) => {
+        const _: () = {
+            #[used]
+            #[link_section = "kernel_tests"]
+            static TEST: $crate::test_registry::TestEntry = $crate::test_registry::TestEntry {
+                name: $name,
+                body: $body,
+                attributes: $attributes,
+            };
+        };
+    };
+}
+
+// the linker defines these around the `kernel_tests` section, weak so an empty section still links
+extern "C" {
+    #[linkage = "extern_weak"]
+    static __start_kernel_tests: *const u8;
+    #[linkage = "extern_weak"]
+    static __stop_kernel_tests: *const u8;
+}
+
+/// Registers every test declared with `kernel_test!`. Needs the heap.
+pub fn register_linked_tests() {
+    let entries = unsafe { crate::linker_section::<TestEntry>(__start_kernel_tests, __stop_kernel_tests) };
+    let mut registry = TEST_REGISTRY.lock();
+    for entry in entries {
+        registry.insert(entry.name, RegisteredTest { body: entry.body, attributes: entry.attributes });
+    }
 }
 
 // A function to register a test
 pub fn register_test(name: &'static str, test: TestFunction) {
-    TEST_REGISTRY.lock().insert(name, test);
+    register_test_with(name, test, TestAttributes::new());
+}
+
+/// Registers a test with attributes.
+pub fn register_test_with(name: &'static str, test: TestFunction, attributes: TestAttributes) {
+    TEST_REGISTRY.lock().insert(name, RegisteredTest { body: TestBody::Sync(test), attributes });
+}
+
+/// Registers an async test, `test` starts it: `register_async_test_with("sleep", || TestFuture::new(sleep_test()), ..)`.
+pub fn register_async_test_with(name: &'static str, test: AsyncTestFunction, attributes: TestAttributes) {
+    TEST_REGISTRY.lock().insert(name, RegisteredTest { body: TestBody::Async(test), attributes });
+}
+
+/// Names of all registered tests with their attributes, sorted.
+pub fn tests() -> Vec<(&'static str, TestAttributes)> {
+    TEST_REGISTRY.lock().iter().map(|(&name, test)| (name, test.attributes)).collect()
+}
+
+/// Which tests `run_matching` runs.
+#[derive(Debug, Clone)]
+pub enum Filter {
+    /// Names matching a glob (`*` and `?` wildcards).
+    Glob(String),
+    /// Tests carrying this tag.
+    Tag(String),
+}
+
+impl Filter {
+    fn matches(&self, name: &str, attributes: &TestAttributes) -> bool {
+        match self {
+            Filter::Glob(pattern) => glob_match(pattern, name),
+            Filter::Tag(tag) => attributes.tags.contains(&tag.as_str()),
+        }
+    }
+}
+
+enum Outcome {
+    Passed,
+    Failed(String),
+    Ignored,
 }
 
 // A function to run a test by name
 pub fn run_test(name: &str) {
-    let test_registry = TEST_REGISTRY.lock();
-    if let Some(test) = test_registry.get(name) {
-        test();
-        println!("Test {} [ok]", name);
-    } else {
-        println!("Test {} not found", name);
+    // copy the test out, so it doesn't run while holding the registry lock
+    let test = TEST_REGISTRY.lock().get_key_value(name).map(|(&name, &test)| (name, test));
+    match test {
+        Some(test) => start_run(vec![test], false),
+        None => error!("Test {} not found", name),
+    }
+}
+
+/// Runs every test selected by `filter` and prints a summary.
+/// A failing test is reported and the run goes on.
+pub fn run_matching(filter: Filter) {
+    let tests: Vec<(&'static str, RegisteredTest)> = TEST_REGISTRY
+        .lock()
+        .iter()
+        .filter(|(name, test)| filter.matches(name, &test.attributes))
+        .map(|(&name, &test)| (name, test))
+        .collect();
+    if tests.is_empty() {
+        error!("No tests match {:?}", filter);
+        return;
+    }
+    start_run(tests, true);
+}
+
+// Async tests have to be awaited, so the tests run in their own task. The shell keeps
+// the keyboard grabbed meanwhile and gets it back (with a new prompt) when the run is over.
+fn start_run(tests: Vec<(&'static str, RegisteredTest)>, summary: bool) {
+    keyboard::grab(Box::new(|_key| true)); // swallow input until the run is done
+    executor::spawn(Task::new(async move {
+        run_tests(tests, summary).await;
+        keyboard::release();
+    }));
+}
+
+async fn run_tests(tests: Vec<(&'static str, RegisteredTest)>, summary: bool) {
+    if summary {
+        println!("Running {} tests", tests.len());
+    }
+    let start = time::ticks();
+    let mut failed = Vec::new();
+    let mut ignored = 0;
+    for &(name, test) in tests.iter() {
+        // a test run by name runs even if it's ignored
+        let outcome = if test.attributes.ignore && summary { Outcome::Ignored } else { run_isolated(test).await };
+        match outcome {
+            Outcome::Failed(_) => failed.push(name),
+            Outcome::Ignored => ignored += 1,
+            Outcome::Passed => {}
+        }
+        report(name, outcome);
+    }
+    if !summary {
+        return;
+    }
+    let elapsed = time::ticks() - start;
+
+    let passed = tests.len() - failed.len() - ignored;
+    let hundredths = elapsed * 100 / time::TIMER_HZ;
+    let summary_color = if failed.is_empty() { Color::LightGreen } else { Color::LightRed };
+    println_colored!(
+        summary_color,
+        "{} passed, {} failed, {} ignored, {}.{:02}s elapsed",
+        passed,
+        failed.len(),
+        ignored,
+        hundredths / 100,
+        hundredths % 100
+    );
+    for name in failed {
+        error!("  failed: {}", name);
+    }
+}
+
+fn report(name: &str, outcome: Outcome) {
+    print!("Test {} ", name);
+    match outcome {
+        Outcome::Passed => println_colored!(Color::LightGreen, "[ok]"),
+        Outcome::Ignored => println_colored!(Color::Yellow, "[ignored]"),
+        Outcome::Failed(message) => {
+            println_colored!(Color::LightRed, "[failed]");
+            error!("  {}", message);
+        }
+    }
+}
+
+// deadline of the running sync test in ticks, 0 if it has none
+static DEADLINE: AtomicU64 = AtomicU64::new(0);
+// set by the timer interrupt once the deadline has passed
+static TIMED_OUT: AtomicBool = AtomicBool::new(false);
+
+/// Called from the timer interrupt: flags the running sync test once its timeout has passed.
+/// The test is stopped at its next `checkpoint`, not right away: jumping out of the interrupted
+/// code could leave a lock (like the heap's) locked for good.
+pub(crate) fn check_timeout() {
+    let deadline = DEADLINE.load(Ordering::SeqCst);
+    if deadline != 0 && time::ticks() >= deadline {
+        TIMED_OUT.store(true, Ordering::SeqCst);
+    }
+}
+
+/// Ends the running sync test as failed if its timeout has passed. Tests that run long call
+/// this where they hold no locks, like once per iteration of their main loop.
+pub fn checkpoint() {
+    if TIMED_OUT.swap(false, Ordering::SeqCst) {
+        DEADLINE.store(0, Ordering::SeqCst);
+        recovery::abort(format_args!("test timed out"));
+    }
+}
+
+// runs one test with panics caught and its timeout armed
+async fn run_isolated(test: RegisteredTest) -> Outcome {
+    let attributes = test.attributes;
+    let result = match test.body {
+        TestBody::Sync(function) => {
+            if let Some(timeout) = attributes.timeout {
+                TIMED_OUT.store(false, Ordering::SeqCst);
+                DEADLINE.store(time::ticks() + timeout.max(1), Ordering::SeqCst);
+            }
+            let result = recovery::catch_panic(function);
+            DEADLINE.store(0, Ordering::SeqCst);
+            // a test that never got to a checkpoint still fails if it took too long
+            match result {
+                Ok(()) if TIMED_OUT.swap(false, Ordering::SeqCst) => Err(String::from("test timed out")),
+                result => result,
+            }
+        }
+        TestBody::Async(start) => {
+            let timeout = attributes.timeout.unwrap_or(ASYNC_TEST_TIMEOUT);
+            match recovery::catch_panic(start) {
+                Ok(future) => Timeout { future, sleep: timer::sleep(timeout) }.await,
+                Err(message) => Err(message),
+            }
+        }
+    };
+
+    match (result, attributes.should_panic) {
+        (Ok(()), None) => Outcome::Passed,
+        (Err(message), None) => Outcome::Failed(message),
+        (Ok(()), Some(_)) => Outcome::Failed(String::from("test did not panic as expected")),
+        (Err(message), Some(expected)) if message.contains(expected) => Outcome::Passed,
+        (Err(message), Some(expected)) => {
+            Outcome::Failed(format!("panic message did not contain \"{}\": {}", expected, message))
+        }
+    }
+}
+
+/// Async tests without a `timeout` attribute fail after this many ticks.
+pub const ASYNC_TEST_TIMEOUT: u64 = 10 * time::TIMER_HZ;
+
+/// Starts an async test.
+pub type AsyncTestFunction = fn() -> TestFuture;
+
+/// A running async test. Every poll is guarded, so a panic fails the test instead of the kernel.
+pub struct TestFuture {
+    future: Option<Pin<Box<dyn Future<Output = ()>>>>,
+}
+
+impl TestFuture {
+    pub fn new(future: impl Future<Output = ()> + 'static) -> TestFuture {
+        TestFuture { future: Some(Box::pin(future)) }
+    }
+}
+
+impl Future for TestFuture {
+    type Output = Result<(), String>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), String>> {
+        let mut future = match self.future.take() {
+            Some(future) => future,
+            None => return Poll::Ready(Ok(())),
+        };
+        match recovery::catch_panic(|| future.as_mut().poll(cx)) {
+            Ok(Poll::Ready(())) => Poll::Ready(Ok(())),
+            Ok(Poll::Pending) => {
+                self.future = Some(future);
+                Poll::Pending
+            }
+            Err(message) => {
+                // the future was abandoned halfway through a poll, dropping it isn't safe
+                core::mem::forget(future);
+                Poll::Ready(Err(message))
+            }
+        }
+    }
+}
+
+// a test future racing against its timeout
+struct Timeout {
+    future: TestFuture,
+    sleep: timer::Sleep,
+}
+
+impl Future for Timeout {
+    type Output = Result<(), String>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), String>> {
+        if let Poll::Ready(result) = Pin::new(&mut self.future).poll(cx) {
+            return Poll::Ready(result);
+        }
+        match Pin::new(&mut self.sleep).poll(cx) {
+            Poll::Ready(()) => Poll::Ready(Err(String::from("test timed out"))),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+/// Matches `name` against `pattern`, where `*` matches any run of characters and `?` exactly one.
+pub fn glob_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    let (mut p, mut n) = (0, 0);
+    // where to retry after the last `*`: its position in the pattern, and how much of the name it ate
+    let mut retry: Option<(usize, usize)> = None;
+
+    while n < name.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == name[n]) {
+            p += 1;
+            n += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            retry = Some((p, n));
+            p += 1;
+        } else if let Some((star, eaten)) = retry {
+            // let the `*` eat one more character
+            p = star + 1;
+            n = eaten + 1;
+            retry = Some((star, eaten + 1));
+        } else {
+            return false;
+        }
     }
+    pattern[p..].iter().all(|&character| character == '*')
 }
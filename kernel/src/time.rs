@@ -0,0 +1,43 @@
+use core::sync::atomic::{AtomicU64, Ordering};
+use x86_64::instructions::port::Port;
+
+const PIT_COMMAND_PORT: u16 = 0x43;
+const PIT_CHANNEL_0_PORT: u16 = 0x40;
+pub const PIT_FREQUENCY: u32 = 1193182; // base frequency of the PIT oscillator in Hz
+
+/// How many timer interrupts fire per second.
+pub const TIMER_HZ: u64 = 100;
+
+static TICKS: AtomicU64 = AtomicU64::new(0);
+
+/// Programs PIT channel 0 to fire the timer interrupt `TIMER_HZ` times per second.
+pub fn init() {
+    let divisor = PIT_FREQUENCY / TIMER_HZ as u32;
+
+    unsafe {
+        // channel 0, low byte then high byte, mode 3 (square wave generator)
+        let mut command_port = Port::<u8>::new(PIT_COMMAND_PORT);
+        command_port.write(0b00110110);
+
+        let mut channel_0_port = Port::<u8>::new(PIT_CHANNEL_0_PORT);
+        channel_0_port.write((divisor & 0xFF) as u8); // Low byte
+        channel_0_port.write((divisor >> 8) as u8); // High byte
+    }
+    crate::log_info!("PIT running at {} Hz", TIMER_HZ);
+}
+
+/// Called by the timer interrupt handler on every tick.
+pub(crate) fn on_tick() {
+    TICKS.fetch_add(1, Ordering::Relaxed);
+    crate::task::timer::wake_sleepers();
+}
+
+/// Number of timer ticks since boot.
+pub fn ticks() -> u64 {
+    TICKS.load(Ordering::Relaxed)
+}
+
+/// Whole seconds since boot.
+pub fn uptime_seconds() -> u64 {
+    ticks() / TIMER_HZ
+}
@@ -0,0 +1,293 @@
+use crate::{console, task::keyboard};
+use crate::vga_buffer::{cp437, ColorCode, Snapshot, Writer, STATUS_ROWS};
+use alloc::{boxed::Box, vec::Vec};
+use core::any::Any;
+use pc_keyboard::DecodedKey;
+use x86_64::instructions::interrupts;
+
+pub mod widgets;
+
+// A small text UI toolkit on top of the console writer: widgets draw onto a Canvas,
+// the Ui owns them, moves focus with Tab and hands every other key to the focused widget.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rect {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Rect {
+    pub const fn new(x: usize, y: usize, width: usize, height: usize) -> Rect {
+        Rect { x, y, width, height }
+    }
+
+    /// The area inside a one character border.
+    pub fn inner(&self) -> Rect {
+        Rect::new(self.x + 1, self.y + 1, self.width.saturating_sub(2), self.height.saturating_sub(2))
+    }
+
+    pub fn right(&self) -> usize {
+        self.x + self.width
+    }
+
+    pub fn bottom(&self) -> usize {
+        self.y + self.height
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BorderStyle {
+    Single,
+    Double,
+}
+
+impl BorderStyle {
+    // corners and lines as CP437 codes: top left, top right, bottom left, bottom right, horizontal, vertical
+    fn glyphs(self) -> [u8; 6] {
+        match self {
+            BorderStyle::Single => [0xDA, 0xBF, 0xC0, 0xD9, 0xC4, 0xB3],
+            BorderStyle::Double => [0xC9, 0xBB, 0xC8, 0xBC, 0xCD, 0xBA],
+        }
+    }
+}
+
+/// Colors shared by the widgets.
+pub mod theme {
+    use crate::vga_buffer::{Color, ColorCode};
+
+    pub const WINDOW: ColorCode = ColorCode::new(Color::White, Color::Blue);
+    pub const BORDER_FOCUSED: ColorCode = ColorCode::new(Color::Yellow, Color::Blue);
+    pub const SELECTED: ColorCode = ColorCode::new(Color::Black, Color::LightCyan);
+    pub const SELECTED_UNFOCUSED: ColorCode = ColorCode::new(Color::Black, Color::LightGray);
+    pub const MENU: ColorCode = ColorCode::new(Color::Black, Color::LightGray);
+    pub const DESKTOP: ColorCode = ColorCode::new(Color::LightGray, Color::Black);
+}
+
+/// The drawing surface widgets get. Coordinates start below the status bar.
+pub struct Canvas<'a> {
+    writer: &'a mut Writer,
+}
+
+impl<'a> Canvas<'a> {
+    /// Columns and rows available for drawing.
+    pub fn size(&self) -> (usize, usize) {
+        (self.writer.width(), self.writer.height() - STATUS_ROWS)
+    }
+
+    pub fn put(&mut self, x: usize, y: usize, glyph: u8, color: ColorCode) {
+        self.writer.put_glyph_at(glyph, color, x, y + STATUS_ROWS);
+    }
+
+    /// Writes `text` on one line, cut off after `max_width` characters.
+    pub fn text(&mut self, x: usize, y: usize, text: &str, max_width: usize, color: ColorCode) {
+        for (offset, character) in text.chars().take(max_width).enumerate() {
+            self.put(x + offset, y, cp437::to_glyph(character), color);
+        }
+    }
+
+    pub fn fill(&mut self, rect: Rect, color: ColorCode) {
+        for y in rect.y..rect.bottom() {
+            for x in rect.x..rect.right() {
+                self.put(x, y, b' ', color);
+            }
+        }
+    }
+
+    pub fn border(&mut self, rect: Rect, style: BorderStyle, color: ColorCode) {
+        if rect.width < 2 || rect.height < 2 {
+            return;
+        }
+        let [top_left, top_right, bottom_left, bottom_right, horizontal, vertical] = style.glyphs();
+        let (right, bottom) = (rect.right() - 1, rect.bottom() - 1);
+
+        for x in rect.x + 1..right {
+            self.put(x, rect.y, horizontal, color);
+            self.put(x, bottom, horizontal, color);
+        }
+        for y in rect.y + 1..bottom {
+            self.put(rect.x, y, vertical, color);
+            self.put(right, y, vertical, color);
+        }
+        self.put(rect.x, rect.y, top_left, color);
+        self.put(right, rect.y, top_right, color);
+        self.put(rect.x, bottom, bottom_left, color);
+        self.put(right, bottom, bottom_right, color);
+    }
+}
+
+/// What a widget did with a key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyResult {
+    Ignored,
+    Consumed,
+    /// Enter was pressed on the item with this index.
+    Activated(usize),
+}
+
+/// Events the Ui reports to its owner.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    /// An item of the widget with id `widget` was activated.
+    Activated { widget: usize, item: usize },
+    /// Escape was pressed.
+    Cancel,
+}
+
+pub trait Widget: Any {
+    fn draw(&self, canvas: &mut Canvas, focused: bool);
+
+    fn handle_key(&mut self, _key: DecodedKey) -> KeyResult {
+        KeyResult::Ignored
+    }
+
+    /// Whether Tab can move the focus onto this widget.
+    fn focusable(&self) -> bool {
+        false
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+/// A set of widgets drawn back to front, with keyboard focus on one of them.
+pub struct Ui {
+    widgets: Vec<Box<dyn Widget + Send>>,
+    focus: Option<usize>,
+    console: usize,
+    saved: Option<Snapshot>, // what the console showed before the Ui opened
+}
+
+impl Ui {
+    /// Creates an empty Ui that draws on the active console.
+    pub fn new() -> Ui {
+        Ui {
+            widgets: Vec::new(),
+            focus: None,
+            console: console::active(),
+            saved: None,
+        }
+    }
+
+    /// Columns and rows the Ui can use.
+    pub fn size(&self) -> (usize, usize) {
+        let writer = console::writer(self.console).lock();
+        (writer.width(), writer.height() - STATUS_ROWS)
+    }
+
+    /// Adds a widget on top of the others and returns its id. The first focusable widget gets the focus.
+    pub fn add(&mut self, widget: impl Widget + Send) -> usize {
+        let id = self.widgets.len();
+        if self.focus.is_none() && widget.focusable() {
+            self.focus = Some(id);
+        }
+        self.widgets.push(Box::new(widget));
+        id
+    }
+
+    /// Returns the widget with the given id if it has type `T`.
+    pub fn widget_mut<T: Widget>(&mut self, id: usize) -> Option<&mut T> {
+        self.widgets.get_mut(id)?.as_any_mut().downcast_mut::<T>()
+    }
+
+    pub fn focus(&self) -> Option<usize> {
+        self.focus
+    }
+
+    pub fn set_focus(&mut self, id: usize) {
+        if self.widgets.get(id).map_or(false, |widget| widget.focusable()) {
+            self.focus = Some(id);
+        }
+    }
+
+    /// Moves the focus to the next focusable widget.
+    pub fn focus_next(&mut self) {
+        let count = self.widgets.len();
+        if count == 0 {
+            return;
+        }
+        let start = self.focus.unwrap_or(count - 1);
+        for step in 1..=count {
+            let id = (start + step) % count;
+            if self.widgets[id].focusable() {
+                self.focus = Some(id);
+                return;
+            }
+        }
+    }
+
+    /// Whether the Ui is on screen.
+    pub fn is_open(&self) -> bool {
+        self.saved.is_some()
+    }
+
+    /// Saves the console content and clears the screen for the Ui.
+    fn open(&mut self) {
+        interrupts::without_interrupts(|| {
+            let mut writer = console::writer(self.console).lock();
+            self.saved = Some(writer.snapshot());
+            let mut canvas = Canvas { writer: &mut writer };
+            let (width, height) = canvas.size();
+            canvas.fill(Rect::new(0, 0, width, height), theme::DESKTOP);
+        });
+    }
+
+    /// Puts back what the console showed before the Ui opened.
+    /// Event handlers call this before printing to the console themselves.
+    pub fn close(&mut self) {
+        if let Some(snapshot) = self.saved.take() {
+            interrupts::without_interrupts(|| {
+                console::writer(self.console).lock().restore(&snapshot);
+            });
+        }
+    }
+
+    pub fn draw(&self) {
+        interrupts::without_interrupts(|| {
+            let mut writer = console::writer(self.console).lock();
+            let mut canvas = Canvas { writer: &mut writer };
+            for (id, widget) in self.widgets.iter().enumerate() {
+                widget.draw(&mut canvas, self.focus == Some(id));
+            }
+        });
+    }
+
+    /// Handles Tab and Escape itself and passes other keys to the focused widget.
+    pub fn handle_key(&mut self, key: DecodedKey) -> Option<Event> {
+        match key {
+            DecodedKey::Unicode('\t') => {
+                self.focus_next();
+                None
+            }
+            DecodedKey::Unicode('\x1b') => Some(Event::Cancel),
+            key => {
+                let id = self.focus?;
+                match self.widgets[id].handle_key(key) {
+                    KeyResult::Activated(item) => Some(Event::Activated { widget: id, item }),
+                    KeyResult::Consumed | KeyResult::Ignored => None,
+                }
+            }
+        }
+    }
+}
+
+/// Shows `ui` full screen and feeds it keyboard input until `on_event` returns false.
+/// The console content is saved first and put back when the Ui closes.
+pub fn run(mut ui: Ui, mut on_event: impl FnMut(&mut Ui, Event) -> bool + Send + 'static) {
+    ui.open();
+    ui.draw();
+
+    keyboard::grab(Box::new(move |key| {
+        let keep_running = match ui.handle_key(key) {
+            Some(event) => on_event(&mut ui, event),
+            None => true,
+        };
+
+        if keep_running {
+            ui.draw();
+        } else {
+            ui.close();
+        }
+        keep_running
+    }));
+}
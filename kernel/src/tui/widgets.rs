@@ -0,0 +1,228 @@
+use super::{theme, BorderStyle, Canvas, KeyResult, Rect, Widget};
+use crate::vga_buffer::ColorCode;
+use alloc::{string::String, vec::Vec};
+use core::any::Any;
+use pc_keyboard::{DecodedKey, KeyCode};
+
+/// A framed box with an optional title, drawn behind the widgets placed on it.
+pub struct Window {
+    pub rect: Rect,
+    pub title: String,
+    pub style: BorderStyle,
+    pub color: ColorCode,
+}
+
+impl Window {
+    pub fn new(rect: Rect, title: &str) -> Window {
+        Window {
+            rect,
+            title: String::from(title),
+            style: BorderStyle::Double,
+            color: theme::WINDOW,
+        }
+    }
+}
+
+impl Widget for Window {
+    fn draw(&self, canvas: &mut Canvas, _focused: bool) {
+        canvas.fill(self.rect, self.color);
+        canvas.border(self.rect, self.style, self.color);
+        if !self.title.is_empty() && self.rect.width > 4 {
+            // centered in the top border, with a space on each side
+            let max_width = self.rect.width - 4;
+            let title_width = self.title.chars().count().min(max_width) + 2;
+            let x = self.rect.x + (self.rect.width - title_width) / 2;
+            canvas.put(x, self.rect.y, b' ', self.color);
+            canvas.text(x + 1, self.rect.y, &self.title, max_width, self.color);
+            canvas.put(x + title_width - 1, self.rect.y, b' ', self.color);
+        }
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+/// A line of static text.
+pub struct Label {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub text: String,
+    pub color: ColorCode,
+}
+
+impl Label {
+    pub fn new(x: usize, y: usize, width: usize, text: &str, color: ColorCode) -> Label {
+        Label { x, y, width, text: String::from(text), color }
+    }
+}
+
+impl Widget for Label {
+    fn draw(&self, canvas: &mut Canvas, _focused: bool) {
+        canvas.fill(Rect::new(self.x, self.y, self.width, 1), self.color);
+        canvas.text(self.x, self.y, &self.text, self.width, self.color);
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+/// A scrollable vertical list with one selected item. Enter activates the selection.
+pub struct ListView {
+    pub rect: Rect,
+    items: Vec<String>,
+    selected: usize,
+    scroll: usize, // index of the first visible item
+}
+
+impl ListView {
+    pub fn new(rect: Rect, items: Vec<String>) -> ListView {
+        ListView { rect, items, selected: 0, scroll: 0 }
+    }
+
+    pub fn items(&self) -> &[String] {
+        &self.items
+    }
+
+    pub fn set_items(&mut self, items: Vec<String>) {
+        self.items = items;
+        self.select(self.selected);
+    }
+
+    pub fn selected(&self) -> Option<usize> {
+        if self.items.is_empty() { None } else { Some(self.selected) }
+    }
+
+    pub fn selected_item(&self) -> Option<&str> {
+        self.items.get(self.selected).map(String::as_str)
+    }
+
+    /// Selects `index` (clamped to the list) and scrolls it into view.
+    pub fn select(&mut self, index: usize) {
+        self.selected = index.min(self.items.len().saturating_sub(1));
+        let visible = self.rect.height.max(1);
+        if self.selected < self.scroll {
+            self.scroll = self.selected;
+        } else if self.selected >= self.scroll + visible {
+            self.scroll = self.selected + 1 - visible;
+        }
+    }
+}
+
+impl Widget for ListView {
+    fn draw(&self, canvas: &mut Canvas, focused: bool) {
+        if self.rect.height == 0 {
+            return;
+        }
+        for line in 0..self.rect.height {
+            let index = self.scroll + line;
+            let color = if index == self.selected && focused {
+                theme::SELECTED
+            } else if index == self.selected {
+                theme::SELECTED_UNFOCUSED
+            } else {
+                theme::WINDOW
+            };
+            let y = self.rect.y + line;
+            canvas.fill(Rect::new(self.rect.x, y, self.rect.width, 1), if index < self.items.len() { color } else { theme::WINDOW });
+            if let Some(item) = self.items.get(index) {
+                canvas.text(self.rect.x + 1, y, item, self.rect.width.saturating_sub(2), color);
+            }
+        }
+
+        // scroll markers when there is more than fits
+        let right = self.rect.right().saturating_sub(1);
+        if self.scroll > 0 {
+            canvas.put(right, self.rect.y, 0x1E, theme::WINDOW); // ▲
+        }
+        if self.scroll + self.rect.height < self.items.len() {
+            canvas.put(right, self.rect.bottom() - 1, 0x1F, theme::WINDOW); // ▼
+        }
+    }
+
+    fn handle_key(&mut self, key: DecodedKey) -> KeyResult {
+        let page = self.rect.height.max(1);
+        match key {
+            DecodedKey::RawKey(KeyCode::ArrowUp) => self.select(self.selected.saturating_sub(1)),
+            DecodedKey::RawKey(KeyCode::ArrowDown) => self.select(self.selected + 1),
+            DecodedKey::RawKey(KeyCode::PageUp) => self.select(self.selected.saturating_sub(page)),
+            DecodedKey::RawKey(KeyCode::PageDown) => self.select(self.selected + page),
+            DecodedKey::RawKey(KeyCode::Home) => self.select(0),
+            DecodedKey::RawKey(KeyCode::End) => self.select(self.items.len()),
+            DecodedKey::Unicode('\n') => {
+                return match self.selected() {
+                    Some(index) => KeyResult::Activated(index),
+                    None => KeyResult::Consumed,
+                };
+            }
+            _ => return KeyResult::Ignored,
+        }
+        KeyResult::Consumed
+    }
+
+    fn focusable(&self) -> bool {
+        true
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+/// A horizontal menu bar. Left/Right move the highlight, Enter activates the item.
+pub struct Menu {
+    pub x: usize,
+    pub y: usize,
+    items: Vec<String>,
+    selected: usize,
+}
+
+impl Menu {
+    pub fn new(x: usize, y: usize, items: &[&str]) -> Menu {
+        Menu {
+            x,
+            y,
+            items: items.iter().map(|&item| String::from(item)).collect(),
+            selected: 0,
+        }
+    }
+
+    pub fn selected(&self) -> usize {
+        self.selected
+    }
+}
+
+impl Widget for Menu {
+    fn draw(&self, canvas: &mut Canvas, focused: bool) {
+        let mut x = self.x;
+        for (index, item) in self.items.iter().enumerate() {
+            let color = if index == self.selected && focused { theme::SELECTED } else { theme::MENU };
+            let width = item.chars().count() + 2;
+            canvas.fill(Rect::new(x, self.y, width, 1), color);
+            canvas.text(x + 1, self.y, item, width, color);
+            x += width + 1;
+        }
+    }
+
+    fn handle_key(&mut self, key: DecodedKey) -> KeyResult {
+        match key {
+            DecodedKey::RawKey(KeyCode::ArrowLeft) => self.selected = self.selected.saturating_sub(1),
+            DecodedKey::RawKey(KeyCode::ArrowRight) => {
+                self.selected = (self.selected + 1).min(self.items.len().saturating_sub(1))
+            }
+            DecodedKey::Unicode('\n') if !self.items.is_empty() => return KeyResult::Activated(self.selected),
+            _ => return KeyResult::Ignored,
+        }
+        KeyResult::Consumed
+    }
+
+    fn focusable(&self) -> bool {
+        true
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
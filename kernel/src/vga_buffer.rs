@@ -1,9 +1,11 @@
 use volatile::Volatile;
-use lazy_static::lazy_static;
 use core::{fmt, panic::PanicInfo};
-use spin::Mutex;
 use x86_64::instructions::port::Port;
-use alloc::format;
+use alloc::{format, string::String, vec::Vec};
+use crate::console;
+
+pub mod cp437;
+pub mod mode;
 
 #[allow(dead_code)] // disabling warnings when compiler sees unused code
 #[derive(Debug, Clone, Copy, PartialEq, Eq)] // enabling copy semantics
@@ -28,17 +30,20 @@ pub enum Color {
 }
 
 const PROMPT_LENGTH: usize = 2; // "> " symbol at start is 2 symbols
-const BUFFER_HEIGHT: usize = 25;
+const BUFFER_HEIGHT: usize = 25; // size of the text mode the BIOS leaves us in
 const BUFFER_WIDTH: usize = 80;
+pub const MAX_HEIGHT: usize = 60; // largest text mode we can switch to, see `mode`
+pub const MAX_WIDTH: usize = 90;
+pub const STATUS_ROWS: usize = 1; // top row is reserved for the status bar and never scrolls
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
-struct ColorCode {
+pub struct ColorCode {
     foreground: Color,
     background: Color,
 }
 
 impl ColorCode {
-    fn new(foreground: Color, background: Color) -> ColorCode {
+    pub const fn new(foreground: Color, background: Color) -> ColorCode {
         ColorCode { foreground, background }
     }
 
@@ -51,7 +56,7 @@ impl ColorCode {
     }
 
     // You'll need this to convert the ColorCode to a u8 for the VGA buffer
-    pub fn to_u8(&self) -> u8 {
+    pub const fn to_u8(&self) -> u8 {
         (self.background as u8) << 4 | (self.foreground as u8)
     }
 }
@@ -63,26 +68,44 @@ struct ScreenChar {
     color_code: u8,
 }
 
-#[repr(transparent)] // ensure that Buffer has the same memory layout as its single field.
+const VGA_BUFFER_ADDRESS: usize = 0xb8000;
+
+/// The actual VGA text memory, only the visible console writes here.
+/// Rows are `width` cells apart, so it is indexed as a flat array.
+#[repr(transparent)] // ensure that HardwareBuffer has the same memory layout as its single field.
+struct HardwareBuffer {
+    chars: [Volatile<ScreenChar>; MAX_WIDTH * MAX_HEIGHT],
+}
+
+/// Off-screen copy of the screen, every console keeps its own.
+/// Big enough for the largest mode, only the top-left `width` x `height` cells are used.
 struct Buffer {
-    chars: [[Volatile<ScreenChar>; BUFFER_WIDTH]; BUFFER_HEIGHT],
+    chars: [[ScreenChar; MAX_WIDTH]; MAX_HEIGHT],
 }
 
-pub struct Writer {
+const BLANK: ScreenChar = ScreenChar {
+    ascii_character: b' ',
+    color_code: ColorCode::new(Color::White, Color::Black).to_u8(),
+};
+
+/// A saved copy of a writer's screen, so full-screen programs can put the console back when they exit.
+pub struct Snapshot {
+    chars: Vec<ScreenChar>,
+    width: usize,
+    height: usize,
     column_position: usize,
     color_code: ColorCode,
-    buffer: &'static mut Buffer,
 }
 
-lazy_static! {
-    pub static ref WRITER: Mutex<Writer> = Mutex::new(Writer {
-        column_position: 0,
-        color_code: ColorCode::new(Color::White, Color::Black),
-        buffer: unsafe { &mut *(0xb8000 as *mut Buffer) },
-    });
+pub struct Writer {
+    column_position: usize,
+    color_code: ColorCode,
+    buffer: Buffer,
+    width: usize,
+    height: usize,
+    visible: bool, // mirror writes to VGA memory only when this console is shown
 }
 
-
 #[macro_export]
 macro_rules! print {
     ($($arg:tt)*) => ($crate::vga_buffer::_print(format_args!($($arg)*)));
@@ -95,36 +118,226 @@ macro_rules! println {
     ($($arg:tt)*) => ($crate::print!("{}\n", format_args!($($arg)*)));
 }
 
+/// Like `print!`, but writes the text with the given foreground `Color`.
+///
+/// The background and the previous color are kept, so only this span of text is colored.
+#[macro_export]
+macro_rules! print_colored {
+    ($color:expr, $($arg:tt)*) => ($crate::vga_buffer::_print_colored($color, format_args!($($arg)*)));
+}
+
+/// Like `println!`, but writes the line with the given foreground `Color`.
+#[macro_export]
+macro_rules! println_colored {
+    ($color:expr) => ($crate::print_colored!($color, "\n"));
+    ($color:expr, $($arg:tt)*) => ($crate::print_colored!($color, "{}\n", format_args!($($arg)*)));
+}
+
+/// Prints a warning line in yellow.
+#[macro_export]
+macro_rules! warn {
+    ($($arg:tt)*) => ($crate::println_colored!($crate::vga_buffer::Color::Yellow, $($arg)*));
+}
+
+/// Prints an error line in light red.
+#[macro_export]
+macro_rules! error {
+    ($($arg:tt)*) => ($crate::println_colored!($crate::vga_buffer::Color::LightRed, $($arg)*));
+}
+
+/// Like `print!`, but writes to the given virtual console instead of the active one.
+#[macro_export]
+macro_rules! print_to {
+    ($console:expr, $($arg:tt)*) => ($crate::vga_buffer::_print_to($console, format_args!($($arg)*)));
+}
+
+/// Like `println!`, but writes to the given virtual console instead of the active one.
+#[macro_export]
+macro_rules! println_to {
+    ($console:expr) => ($crate::print_to!($console, "\n"));
+    ($console:expr, $($arg:tt)*) => ($crate::print_to!($console, "{}\n", format_args!($($arg)*)));
+}
+
 /// Prints the given formatted string to the VGA text buffer
-/// through the global `WRITER` instance.
+/// through the writer of the active console, and mirrors it to COM1.
 #[doc(hidden)]
 pub fn _print(args: fmt::Arguments) {
+    _print_to(console::active(), args);
+    crate::serial::_print(args);
+    capture(args);
+}
+
+// output collected for tests, None while nobody is capturing
+static CAPTURE: spin::Mutex<Option<String>> = spin::Mutex::new(None);
+
+/// Starts collecting everything printed to the active console (`print!` and friends).
+/// Meant for tests that check what a command printed.
+pub fn start_capture() {
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        *CAPTURE.lock() = Some(String::new());
+    });
+}
+
+/// Stops collecting output and returns what was printed since `start_capture`.
+pub fn take_capture() -> String {
+    x86_64::instructions::interrupts::without_interrupts(|| CAPTURE.lock().take().unwrap_or_default())
+}
+
+fn capture(args: fmt::Arguments) {
+    use core::fmt::Write;
+
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        if let Some(output) = CAPTURE.lock().as_mut() {
+            let _ = output.write_fmt(args);
+        }
+    });
+}
+
+/// Prints the given formatted string to the writer of the given console.
+#[doc(hidden)]
+pub fn _print_to(console: usize, args: fmt::Arguments) {
     use core::fmt::Write;
     use x86_64::instructions::interrupts;
 
     interrupts::without_interrupts(|| {
-        WRITER.lock().write_fmt(args).unwrap();
+        console::writer(console).lock().write_fmt(args).unwrap();
     });
 }
 
+/// Prints the given formatted string with a temporary foreground color,
+/// restoring the previous `ColorCode` afterwards.
+#[doc(hidden)]
+pub fn _print_colored(foreground: Color, args: fmt::Arguments) {
+    use core::fmt::Write;
+    use x86_64::instructions::interrupts;
+
+    interrupts::without_interrupts(|| {
+        let mut writer = console::active_writer().lock();
+        let previous = writer.color_code();
+        writer.set_color(ColorCode::new(foreground, previous.get_background()));
+        writer.write_fmt(args).unwrap();
+        writer.set_color(previous);
+    });
+    crate::serial::_print(args);
+    capture(args);
+}
+
 #[allow(dead_code)]
 impl Writer {
+    /// Creates a blank 80x25 writer.
+    pub const fn new(visible: bool) -> Writer {
+        Writer {
+            column_position: 0,
+            color_code: ColorCode::new(Color::White, Color::Black),
+            buffer: Buffer { chars: [[BLANK; MAX_WIDTH]; MAX_HEIGHT] },
+            width: BUFFER_WIDTH,
+            height: BUFFER_HEIGHT,
+            visible,
+        }
+    }
+
+    fn hardware_buffer() -> &'static mut HardwareBuffer {
+        unsafe { &mut *(VGA_BUFFER_ADDRESS as *mut HardwareBuffer) }
+    }
+
+    fn read_char(&self, row: usize, col: usize) -> ScreenChar {
+        self.buffer.chars[row][col]
+    }
+
+    /// Writes a character to the off-screen buffer, and to VGA memory if this writer is visible.
+    fn write_char(&mut self, row: usize, col: usize, character: ScreenChar) {
+        self.buffer.chars[row][col] = character;
+        if self.visible {
+            Self::hardware_buffer().chars[row * self.width + col].write(character);
+        }
+    }
+
+    /// Copies the whole off-screen buffer into VGA memory and moves the hardware cursor.
+    pub fn present(&mut self) {
+        let hardware = Self::hardware_buffer();
+        for row in 0..self.height {
+            for col in 0..self.width {
+                hardware.chars[row * self.width + col].write(self.buffer.chars[row][col]);
+            }
+        }
+        self.update_cursor();
+    }
+
+    /// Shows or hides this writer. A writer that becomes visible is blitted to the screen.
+    pub fn set_visible(&mut self, visible: bool) {
+        self.visible = visible;
+        if visible {
+            self.present();
+        }
+    }
+
+    pub fn is_visible(&self) -> bool {
+        self.visible
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Changes the text area to `width` x `height` cells after a mode switch.
+    ///
+    /// The status row stays on top and the text is kept aligned to the bottom row,
+    /// where the cursor lives, so the prompt and the latest output survive.
+    pub fn resize(&mut self, width: usize, height: usize) {
+        let width = width.min(MAX_WIDTH);
+        let height = height.min(MAX_HEIGHT);
+        let shift = height as isize - self.height as isize; // how far rows move down
+        let old_width = self.width;
+
+        let move_row = |buffer: &mut Buffer, row: usize| {
+            let source = row as isize - shift;
+            for col in 0..width {
+                buffer.chars[row][col] = if source >= STATUS_ROWS as isize && col < old_width {
+                    buffer.chars[source as usize][col]
+                } else {
+                    BLANK
+                };
+            }
+        };
+        // walk in the direction that doesn't overwrite rows we still have to read
+        if shift > 0 {
+            for row in (STATUS_ROWS..height).rev() {
+                move_row(&mut self.buffer, row);
+            }
+        } else {
+            for row in STATUS_ROWS..height {
+                move_row(&mut self.buffer, row);
+            }
+        }
+
+        self.width = width;
+        self.height = height;
+        self.column_position = self.column_position.min(width);
+        if self.visible {
+            self.present();
+        }
+    }
+
     /// Writes an ASCII byte to the buffer.
     ///
-    /// Wraps lines at `BUFFER_WIDTH`. Supports the `\n` newline character.
+    /// Wraps lines at the screen width. Supports the `\n` newline character.
     pub fn write_byte(&mut self, byte: u8) {
         match byte {
             b'\n' => self.new_line(),
             byte => {
-                if self.column_position >= BUFFER_WIDTH {
+                if self.column_position >= self.width {
                     self.new_line();
                 }
 
-                let row = BUFFER_HEIGHT - 1;
+                let row = self.height - 1;
                 let col = self.column_position;
 
                 let color_code = self.color_code.to_u8();
-                self.buffer.chars[row][col].write(ScreenChar {
+                self.write_char(row, col, ScreenChar {
                     ascii_character: byte,
                     color_code,
                 });
@@ -135,18 +348,15 @@ impl Writer {
         self.update_cursor();
     }
 
-    /// Writes the given ASCII string to the buffer.
+    /// Writes the given string to the buffer.
     ///
-    /// Wraps lines at `BUFFER_WIDTH`. Supports the `\n` newline character. Does **not**
-    /// support strings with non-ASCII characters, since they can't be printed in the VGA text
-    /// mode.
+    /// Wraps lines at the screen width. Supports the `\n` newline character. Characters are
+    /// translated to code page 437, the ones the VGA font has no glyph for are printed as `■`.
     fn write_string(&mut self, s: &str) {
-        for byte in s.bytes() {
-            match byte {
-                // printable ASCII byte or newline
-                0x20..=0x7e | b'\n' => self.write_byte(byte),
-                // not part of printable ASCII range
-                _ => self.write_byte(0xfe),
+        for character in s.chars() {
+            match character {
+                '\n' => self.write_byte(b'\n'),
+                character => self.write_byte(cp437::to_glyph(character)),
             }
         }
     }
@@ -154,48 +364,97 @@ impl Writer {
     // Writes a byte at a specific position without altering the cursor or column position
     pub fn write_byte_at(&mut self, byte: u8, column: usize, row: usize) {
         let color_code = self.color_code.to_u8();
-        self.buffer.chars[row][column].write(ScreenChar {
+        self.write_char(row, column, ScreenChar {
             ascii_character: byte,
             color_code,
         });
     }
 
+    /// Writes a CP437 glyph with its own color at a specific position, without moving the cursor.
+    /// Positions outside the screen are ignored.
+    pub fn put_glyph_at(&mut self, glyph: u8, color_code: ColorCode, column: usize, row: usize) {
+        if column < self.width && row < self.height {
+            self.write_char(row, column, ScreenChar {
+                ascii_character: glyph,
+                color_code: color_code.to_u8(),
+            });
+        }
+    }
+
     pub fn write_string_at(&mut self, s: &str, mut column: usize, mut row: usize) {
-        for byte in s.bytes() {
-            if byte == b'\n' {
+        for character in s.chars() {
+            if character == '\n' {
                 // If newline, move to the next line and reset column
                 row += 1;
                 column = 0;
                 continue;
             }
     
-            if column >= BUFFER_WIDTH {
+            if column >= self.width {
                 // If end of line, move to the next line
                 row += 1;
                 column = 0;
             }
     
-            if row >= BUFFER_HEIGHT {
+            if row >= self.height {
                 // Scroll up the text to make room for more text
                 self.new_line();
-                row = BUFFER_HEIGHT - 1; // Set row to the last line after scrolling
+                row = self.height - 1; // Set row to the last line after scrolling
             }
     
-            // Write the byte and increment the column
-            self.write_byte_at(byte, column, row);
+            // Write the glyph and increment the column
+            self.write_byte_at(cp437::to_glyph(character), column, row);
             column += 1;
         }
     }        
 
-    /// Shifts all lines one line up and clears the last row.
+    /// Returns the text shown in `row`, trailing blanks removed.
+    pub fn row_text(&self, row: usize) -> String {
+        let text: String = self.buffer.chars[row][..self.width]
+            .iter()
+            .map(|character| cp437::to_char(character.ascii_character))
+            .collect();
+        String::from(text.trim_end())
+    }
+
+    /// Saves the current screen content, cursor and color.
+    pub fn snapshot(&self) -> Snapshot {
+        let mut chars = Vec::with_capacity(self.width * self.height);
+        for row in 0..self.height {
+            chars.extend_from_slice(&self.buffer.chars[row][..self.width]);
+        }
+        Snapshot {
+            chars,
+            width: self.width,
+            height: self.height,
+            column_position: self.column_position,
+            color_code: self.color_code,
+        }
+    }
+
+    /// Puts back a screen saved with `snapshot`. The status row is left alone, the status bar owns it.
+    pub fn restore(&mut self, snapshot: &Snapshot) {
+        for row in STATUS_ROWS..self.height.min(snapshot.height) {
+            for col in 0..self.width.min(snapshot.width) {
+                self.buffer.chars[row][col] = snapshot.chars[row * snapshot.width + col];
+            }
+        }
+        self.column_position = snapshot.column_position.min(self.width);
+        self.color_code = snapshot.color_code;
+        if self.visible {
+            self.present();
+        }
+    }
+
+    /// Shifts all lines below the status bar one line up and clears the last row.
     fn new_line(&mut self) {
-        for row in 1..BUFFER_HEIGHT {
-            for col in 0..BUFFER_WIDTH {
-                let character = self.buffer.chars[row][col].read();
-                self.buffer.chars[row - 1][col].write(character);
+        for row in STATUS_ROWS + 1..self.height {
+            for col in 0..self.width {
+                let character = self.read_char(row, col);
+                self.write_char(row - 1, col, character);
             }
         }
-        self.clear_row(BUFFER_HEIGHT - 1);
+        self.clear_row(self.height - 1);
         self.column_position = 0;
 
         self.update_cursor();
@@ -207,8 +466,8 @@ impl Writer {
             ascii_character: b' ',
             color_code: self.color_code.to_u8(),
         };
-        for col in 0..BUFFER_WIDTH {
-            self.buffer.chars[row][col].write(blank);
+        for col in 0..self.width {
+            self.write_char(row, col, blank);
         }
     }
 
@@ -227,9 +486,9 @@ impl Writer {
             self.column_position -= 1; // Move back again to overwrite the same position next time
         } else if self.column_position == PROMPT_LENGTH {
             // Check if the last two characters are the prompt
-            let row = BUFFER_HEIGHT - 1; // Assuming current row is always the last one
-            let prev_char = self.buffer.chars[row][self.column_position - 1].read();
-            let prev_prev_char = self.buffer.chars[row][self.column_position - 2].read();
+            let row = self.height - 1; // Assuming current row is always the last one
+            let prev_char = self.read_char(row, self.column_position - 1);
+            let prev_prev_char = self.read_char(row, self.column_position - 2);
             
             if !(prev_char.ascii_character == b' ' && prev_prev_char.ascii_character == b'>') {
                 // If the last two characters are not the prompt, remove the last symbol
@@ -240,25 +499,35 @@ impl Writer {
         } else if self.column_position <= 1 {
             // If we are at the start of a line (after a newline), move the text down
             self.move_text_down();
-            self.column_position = BUFFER_WIDTH; // Set cursor to the end of the previous line
+            self.column_position = self.width; // Set cursor to the end of the previous line
         }
 
         self.update_cursor();
     }           
 
-    /// Moves all text down by one line, creating space at the top row.
+    /// Moves all text down by one line, creating space at the first row below the status bar.
     fn move_text_down(&mut self) {
-        for row in (1..BUFFER_HEIGHT).rev() {
-            for col in 0..BUFFER_WIDTH {
-                let character = self.buffer.chars[row - 1][col].read();
-                self.buffer.chars[row][col].write(character);
+        for row in (STATUS_ROWS + 1..self.height).rev() {
+            for col in 0..self.width {
+                let character = self.read_char(row - 1, col);
+                self.write_char(row, col, character);
             }
         }
     }
 
     fn update_cursor(&self) {
-        let row = BUFFER_HEIGHT - 1; // The current row is always the last one
-        let position = row * BUFFER_WIDTH + self.column_position;
+        // The current row is always the last one
+        self.place_cursor(self.column_position, self.height - 1);
+    }
+
+    /// Moves the hardware cursor to the given cell without touching the write position.
+    /// Full-screen programs use this to show their own cursor.
+    pub fn place_cursor(&self, column: usize, row: usize) {
+        if !self.visible {
+            return; // the hardware cursor belongs to the visible console
+        }
+
+        let position = row * self.width + column;
     
         // VGA control registers
         let vga_index_port = 0x3D4;
@@ -278,15 +547,33 @@ impl Writer {
     }   
 
     /// Sets the color code for the Writer.
-    fn set_color(&mut self, color_code: ColorCode) {
+    pub fn set_color(&mut self, color_code: ColorCode) {
         self.color_code = color_code;
     }
 
+    /// Returns the color code the Writer currently uses.
+    pub fn color_code(&self) -> ColorCode {
+        self.color_code
+    }
+
+    /// Overwrites the reserved status row with `text`, padded or cut to the screen width.
+    pub fn write_status_line(&mut self, text: &str) {
+        let color_code = ColorCode::new(Color::Black, Color::LightGray).to_u8();
+        let mut characters = text.chars();
+        for col in 0..self.width {
+            let byte = characters.next().map_or(b' ', cp437::to_glyph);
+            self.write_char(0, col, ScreenChar {
+                ascii_character: byte,
+                color_code,
+            });
+        }
+    }
+
     pub fn set_screen_color(&mut self, background_color: Color) {
-        for row in 0..BUFFER_HEIGHT {
-            for col in 0..BUFFER_WIDTH {
+        for row in 0..self.height {
+            for col in 0..self.width {
                 let color_code = ColorCode::new(self.color_code.get_foreground(), background_color).to_u8();
-                self.buffer.chars[row][col].write(ScreenChar {
+                self.write_char(row, col, ScreenChar {
                     ascii_character: b' ',
                     color_code,
                 });
@@ -304,8 +591,8 @@ impl Writer {
     pub fn bsod_title(&mut self) {
         // Calculate the position to center the "VertexDOS panicked" message
         let message = "VertexDOS panicked";
-        let row = BUFFER_HEIGHT / 3; // Slightly more to the top
-        let column = (BUFFER_WIDTH - message.len()) / 2; // Centered
+        let row = self.height / 3; // Slightly more to the top
+        let column = self.width.saturating_sub(message.len()) / 2; // Centered
     
         // Set the color for the text
         self.set_color(ColorCode::new(Color::Blue, Color::White));
@@ -316,8 +603,8 @@ impl Writer {
     
     pub fn bsod_panic_message(&mut self, info: &PanicInfo) {
         let message = format!("{}", info);
-        let row = BUFFER_HEIGHT / 2; // Start in the middle of the screen
-        let column = (BUFFER_WIDTH - message.len()) / 3;
+        let row = self.height / 2; // Start in the middle of the screen
+        let column = self.width.saturating_sub(message.len()) / 3;
 
         self.set_color(ColorCode::new(Color::White, Color::Blue));
 
@@ -0,0 +1,64 @@
+// Translation from Unicode to code page 437, the character set baked into the VGA text mode font.
+
+/// Glyph used for characters that have no CP437 equivalent (■).
+pub const FALLBACK_GLYPH: u8 = 0xfe;
+
+// glyphs the font has for the control range 0x01..=0x1F, index 0 is never used
+const LOW_GLYPHS: [char; 32] = [
+    '\0', '☺', '☻', '♥', '♦', '♣', '♠', '•', '◘', '○', '◙', '♂', '♀', '♪', '♫', '☼',
+    '►', '◄', '↕', '‼', '¶', '§', '▬', '↨', '↑', '↓', '→', '←', '∟', '↔', '▲', '▼',
+];
+
+// glyphs 0x80..=0xFF
+const HIGH_GLYPHS: [char; 128] = [
+    'Ç', 'ü', 'é', 'â', 'ä', 'à', 'å', 'ç', 'ê', 'ë', 'è', 'ï', 'î', 'ì', 'Ä', 'Å',
+    'É', 'æ', 'Æ', 'ô', 'ö', 'ò', 'û', 'ù', 'ÿ', 'Ö', 'Ü', '¢', '£', '¥', '₧', 'ƒ',
+    'á', 'í', 'ó', 'ú', 'ñ', 'Ñ', 'ª', 'º', '¿', '⌐', '¬', '½', '¼', '¡', '«', '»',
+    '░', '▒', '▓', '│', '┤', '╡', '╢', '╖', '╕', '╣', '║', '╗', '╝', '╜', '╛', '┐',
+    '└', '┴', '┬', '├', '─', '┼', '╞', '╟', '╚', '╔', '╩', '╦', '╠', '═', '╬', '╧',
+    '╨', '╤', '╥', '╙', '╘', '╒', '╓', '╫', '╪', '┘', '┌', '█', '▄', '▌', '▐', '▀',
+    'α', 'ß', 'Γ', 'π', 'Σ', 'σ', 'µ', 'τ', 'Φ', 'Θ', 'Ω', 'δ', '∞', 'φ', 'ε', '∩',
+    '≡', '±', '≥', '≤', '⌠', '⌡', '÷', '≈', '°', '∙', '·', '√', 'ⁿ', '²', '■', '\u{a0}',
+];
+
+/// Returns the CP437 code for `character`, or `None` if the font has no glyph for it.
+///
+/// Printable ASCII maps to itself, control characters (except the ones that have a
+/// symbol glyph) have no mapping.
+pub fn from_char(character: char) -> Option<u8> {
+    match character {
+        ' '..='~' => Some(character as u8),
+        '⌂' => Some(0x7f),
+        // look-alikes that share a glyph
+        'β' => Some(0xe1),
+        'μ' => Some(0xe6),
+        '∑' => Some(0xe4),
+        'Ø' | '∅' => Some(0xed),
+        '∈' | '€' => Some(0xee),
+        _ => {
+            if let Some(index) = LOW_GLYPHS.iter().skip(1).position(|&c| c == character) {
+                return Some(index as u8 + 1);
+            }
+            HIGH_GLYPHS
+                .iter()
+                .position(|&c| c == character)
+                .map(|index| index as u8 + 0x80)
+        }
+    }
+}
+
+/// Like `from_char`, but substitutes `FALLBACK_GLYPH` for unmappable characters.
+pub fn to_glyph(character: char) -> u8 {
+    from_char(character).unwrap_or(FALLBACK_GLYPH)
+}
+
+/// Returns the Unicode character the font draws for a CP437 code. 0 (blank) reads as a space.
+pub fn to_char(glyph: u8) -> char {
+    match glyph {
+        0 => ' ',
+        0x01..=0x1f => LOW_GLYPHS[glyph as usize],
+        0x7f => '⌂',
+        0x80..=0xff => HIGH_GLYPHS[glyph as usize - 0x80],
+        _ => glyph as char,
+    }
+}
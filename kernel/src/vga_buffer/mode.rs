@@ -0,0 +1,332 @@
+use crate::console;
+use spin::Mutex;
+use x86_64::instructions::{interrupts, port::Port};
+
+// VGA register programming for the text modes we support, plus loading fonts into plane 2.
+// Register values are the classic ones from Chris Giese's modes.c.
+
+const VGA_AC_INDEX: u16 = 0x3C0;
+const VGA_AC_WRITE: u16 = 0x3C0;
+const VGA_MISC_WRITE: u16 = 0x3C2;
+const VGA_SEQ_INDEX: u16 = 0x3C4;
+const VGA_SEQ_DATA: u16 = 0x3C5;
+const VGA_GC_INDEX: u16 = 0x3CE;
+const VGA_GC_DATA: u16 = 0x3CF;
+const VGA_CRTC_INDEX: u16 = 0x3D4;
+const VGA_CRTC_DATA: u16 = 0x3D5;
+const VGA_INSTAT_READ: u16 = 0x3DA;
+
+const NUM_SEQ_REGS: usize = 5;
+const NUM_CRTC_REGS: usize = 25;
+const NUM_GC_REGS: usize = 9;
+const NUM_AC_REGS: usize = 21;
+pub const NUM_REGS: usize = 1 + NUM_SEQ_REGS + NUM_CRTC_REGS + NUM_GC_REGS + NUM_AC_REGS;
+
+/// A full register set: misc output, sequencer, CRT controller, graphics controller, attribute controller.
+pub type RegisterSet = [u8; NUM_REGS];
+
+const REGS_80X25: RegisterSet = [
+    // MISC
+    0x67,
+    // SEQ
+    0x03, 0x00, 0x03, 0x00, 0x02,
+    // CRTC
+    0x5F, 0x4F, 0x50, 0x82, 0x55, 0x81, 0xBF, 0x1F,
+    0x00, 0x4F, 0x0D, 0x0E, 0x00, 0x00, 0x00, 0x50,
+    0x9C, 0x0E, 0x8F, 0x28, 0x1F, 0x96, 0xB9, 0xA3,
+    0xFF,
+    // GC
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x10, 0x0E, 0x00,
+    0xFF,
+    // AC
+    0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x14, 0x07,
+    0x38, 0x39, 0x3A, 0x3B, 0x3C, 0x3D, 0x3E, 0x3F,
+    0x0C, 0x00, 0x0F, 0x08, 0x00,
+];
+
+const REGS_80X50: RegisterSet = [
+    // MISC
+    0x67,
+    // SEQ
+    0x03, 0x00, 0x03, 0x00, 0x02,
+    // CRTC
+    0x5F, 0x4F, 0x50, 0x82, 0x55, 0x81, 0xBF, 0x1F,
+    0x00, 0x47, 0x06, 0x07, 0x00, 0x00, 0x01, 0x40,
+    0x9C, 0x8E, 0x8F, 0x28, 0x1F, 0x96, 0xB9, 0xA3,
+    0xFF,
+    // GC
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x10, 0x0E, 0x00,
+    0xFF,
+    // AC
+    0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x14, 0x07,
+    0x38, 0x39, 0x3A, 0x3B, 0x3C, 0x3D, 0x3E, 0x3F,
+    0x0C, 0x00, 0x0F, 0x08, 0x00,
+];
+
+const REGS_90X60: RegisterSet = [
+    // MISC: 28 MHz dot clock for 720 pixel wide lines
+    0xE7,
+    // SEQ: 8 dot wide characters
+    0x03, 0x01, 0x03, 0x00, 0x02,
+    // CRTC
+    0x6B, 0x59, 0x5A, 0x82, 0x60, 0x8D, 0x0B, 0x3E,
+    0x00, 0x47, 0x06, 0x07, 0x00, 0x00, 0x00, 0x00,
+    0xEA, 0x0C, 0xDF, 0x2D, 0x08, 0xE8, 0x05, 0xA3,
+    0xFF,
+    // GC
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x10, 0x0E, 0x00,
+    0xFF,
+    // AC
+    0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x14, 0x07,
+    0x38, 0x39, 0x3A, 0x3B, 0x3C, 0x3D, 0x3E, 0x3F,
+    0x0C, 0x00, 0x0F, 0x08, 0x00,
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextMode {
+    Text80x25,
+    Text80x50,
+    Text90x60,
+}
+
+impl TextMode {
+    pub const ALL: [TextMode; 3] = [TextMode::Text80x25, TextMode::Text80x50, TextMode::Text90x60];
+
+    /// Parses names like `80x50`.
+    pub fn from_name(name: &str) -> Option<TextMode> {
+        TextMode::ALL.iter().copied().find(|mode| mode.name() == name)
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            TextMode::Text80x25 => "80x25",
+            TextMode::Text80x50 => "80x50",
+            TextMode::Text90x60 => "90x60",
+        }
+    }
+
+    pub fn columns(self) -> usize {
+        match self {
+            TextMode::Text80x25 | TextMode::Text80x50 => 80,
+            TextMode::Text90x60 => 90,
+        }
+    }
+
+    pub fn rows(self) -> usize {
+        match self {
+            TextMode::Text80x25 => 25,
+            TextMode::Text80x50 => 50,
+            TextMode::Text90x60 => 60,
+        }
+    }
+
+    /// Height of a character cell in scan lines, the loaded font must match it.
+    pub fn font_height(self) -> usize {
+        match self {
+            TextMode::Text80x25 => 16,
+            TextMode::Text80x50 | TextMode::Text90x60 => 8,
+        }
+    }
+
+    fn registers(self) -> &'static RegisterSet {
+        match self {
+            TextMode::Text80x25 => &REGS_80X25,
+            TextMode::Text80x50 => &REGS_80X50,
+            TextMode::Text90x60 => &REGS_90X60,
+        }
+    }
+}
+
+/// A bitmap font: 256 glyphs of `height` bytes each, one byte per scan line, MSB is the leftmost pixel.
+pub struct Font<'a> {
+    pub height: usize,
+    pub glyphs: &'a [u8],
+}
+
+const GLYPH_STRIDE: usize = 32; // glyphs are 32 bytes apart in plane 2, whatever the font height
+const FONT_PLANE: u8 = 2;
+
+struct Fonts {
+    bios_8x16: [u8; 256 * 16],
+    derived_8x8: [u8; 256 * 8],
+}
+
+static FONTS: Mutex<Fonts> = Mutex::new(Fonts {
+    bios_8x16: [0; 256 * 16],
+    derived_8x8: [0; 256 * 8],
+});
+static ACTIVE_MODE: Mutex<TextMode> = Mutex::new(TextMode::Text80x25);
+
+/// Saves the 8x16 font the BIOS loaded, and derives an 8x8 font from it for the 50 and 60 row modes.
+///
+/// Must run before the first mode switch, since switching overwrites plane 2.
+pub fn init() {
+    interrupts::without_interrupts(|| {
+        let mut fonts = FONTS.lock();
+        read_font(&mut fonts.bios_8x16, 16);
+
+        // squash every pair of scan lines into one, OR-ing keeps thin strokes visible
+        let Fonts { bios_8x16, derived_8x8 } = &mut *fonts;
+        for glyph in 0..256 {
+            for line in 0..8 {
+                derived_8x8[glyph * 8 + line] =
+                    bios_8x16[glyph * 16 + line * 2] | bios_8x16[glyph * 16 + line * 2 + 1];
+            }
+        }
+    });
+}
+
+/// Returns the text mode that is currently programmed.
+pub fn active_mode() -> TextMode {
+    *ACTIVE_MODE.lock()
+}
+
+/// Programs the VGA for `mode`, loads a matching font and resizes every console.
+pub fn set_text_mode(mode: TextMode) {
+    interrupts::without_interrupts(|| {
+        unsafe { write_registers(mode.registers()) };
+        reload_font(mode);
+        *ACTIVE_MODE.lock() = mode;
+    });
+    console::resize_all(mode.columns(), mode.rows());
+}
+
+/// Re-programs the active text mode, for example after a graphics mode clobbered the registers and the font.
+pub fn restore_text_mode() {
+    set_text_mode(active_mode());
+}
+
+fn reload_font(mode: TextMode) {
+    let fonts = FONTS.lock();
+    let glyphs: &[u8] = match mode.font_height() {
+        16 => &fonts.bios_8x16,
+        _ => &fonts.derived_8x8,
+    };
+    load_font(&Font { height: mode.font_height(), glyphs });
+}
+
+/// Returns the 8x8 bitmap of the given CP437 character, for drawing text in graphics modes.
+pub fn glyph_8x8(character: u8) -> [u8; 8] {
+    let fonts = FONTS.lock();
+    let start = character as usize * 8;
+    let mut glyph = [0; 8];
+    glyph.copy_from_slice(&fonts.derived_8x8[start..start + 8]);
+    glyph
+}
+
+/// Loads a custom font into plane 2. Its height has to match the active mode's character height.
+pub fn load_font(font: &Font) {
+    assert!(font.height <= GLYPH_STRIDE, "font too tall");
+    assert!(font.glyphs.len() >= 256 * font.height, "font needs 256 glyphs");
+
+    interrupts::without_interrupts(|| unsafe {
+        with_font_plane(|plane| {
+            for glyph in 0..256 {
+                for line in 0..GLYPH_STRIDE {
+                    let byte = if line < font.height { font.glyphs[glyph * font.height + line] } else { 0 };
+                    plane.add(glyph * GLYPH_STRIDE + line).write_volatile(byte);
+                }
+            }
+        });
+    });
+}
+
+fn read_font(glyphs: &mut [u8], height: usize) {
+    unsafe {
+        with_font_plane(|plane| {
+            for glyph in 0..256 {
+                for line in 0..height {
+                    glyphs[glyph * height + line] = plane.add(glyph * GLYPH_STRIDE + line).read_volatile();
+                }
+            }
+        });
+    }
+}
+
+/// Switches the CPU's view of video memory to plane 2 with flat addressing, runs `f` with a
+/// pointer to its start and restores the text mode addressing afterwards.
+unsafe fn with_font_plane(f: impl FnOnce(*mut u8)) {
+    let seq2 = read_indexed(VGA_SEQ_INDEX, VGA_SEQ_DATA, 2);
+    let seq4 = read_indexed(VGA_SEQ_INDEX, VGA_SEQ_DATA, 4);
+    let gc4 = read_indexed(VGA_GC_INDEX, VGA_GC_DATA, 4);
+    let gc5 = read_indexed(VGA_GC_INDEX, VGA_GC_DATA, 5);
+    let gc6 = read_indexed(VGA_GC_INDEX, VGA_GC_DATA, 6);
+
+    // turn off even-odd addressing
+    write_indexed(VGA_SEQ_INDEX, VGA_SEQ_DATA, 4, seq4 | 0x04);
+    write_indexed(VGA_GC_INDEX, VGA_GC_DATA, 5, gc5 & !0x10);
+    write_indexed(VGA_GC_INDEX, VGA_GC_DATA, 6, gc6 & !0x02);
+    // read and write only plane 2
+    write_indexed(VGA_SEQ_INDEX, VGA_SEQ_DATA, 2, 1 << FONT_PLANE);
+    write_indexed(VGA_GC_INDEX, VGA_GC_DATA, 4, FONT_PLANE);
+
+    // text modes map video memory at 0xb8000
+    f(0xb8000 as *mut u8);
+
+    write_indexed(VGA_SEQ_INDEX, VGA_SEQ_DATA, 2, seq2);
+    write_indexed(VGA_SEQ_INDEX, VGA_SEQ_DATA, 4, seq4);
+    write_indexed(VGA_GC_INDEX, VGA_GC_DATA, 4, gc4);
+    write_indexed(VGA_GC_INDEX, VGA_GC_DATA, 5, gc5);
+    write_indexed(VGA_GC_INDEX, VGA_GC_DATA, 6, gc6);
+}
+
+unsafe fn read_indexed(index_port: u16, data_port: u16, index: u8) -> u8 {
+    Port::<u8>::new(index_port).write(index);
+    Port::<u8>::new(data_port).read()
+}
+
+unsafe fn write_indexed(index_port: u16, data_port: u16, index: u8, value: u8) {
+    Port::<u8>::new(index_port).write(index);
+    Port::<u8>::new(data_port).write(value);
+}
+
+/// Writes a full register set to the VGA.
+///
+/// Unsafe because wrong values can put the card into a mode the monitor can't display.
+pub unsafe fn write_registers(registers: &RegisterSet) {
+    let mut registers = *registers;
+    let mut misc = Port::<u8>::new(VGA_MISC_WRITE);
+    misc.write(registers[0]);
+
+    let seq = &registers[1..1 + NUM_SEQ_REGS];
+    for (index, &value) in seq.iter().enumerate() {
+        write_indexed(VGA_SEQ_INDEX, VGA_SEQ_DATA, index as u8, value);
+    }
+
+    // unlock CRTC registers 0-7 and keep them unlocked
+    let crtc_start = 1 + NUM_SEQ_REGS;
+    let crtc3 = read_indexed(VGA_CRTC_INDEX, VGA_CRTC_DATA, 0x03);
+    write_indexed(VGA_CRTC_INDEX, VGA_CRTC_DATA, 0x03, crtc3 | 0x80);
+    let crtc11 = read_indexed(VGA_CRTC_INDEX, VGA_CRTC_DATA, 0x11);
+    write_indexed(VGA_CRTC_INDEX, VGA_CRTC_DATA, 0x11, crtc11 & !0x80);
+    registers[crtc_start + 0x03] |= 0x80;
+    registers[crtc_start + 0x11] &= !0x80;
+
+    let crtc = &registers[crtc_start..crtc_start + NUM_CRTC_REGS];
+    for (index, &value) in crtc.iter().enumerate() {
+        write_indexed(VGA_CRTC_INDEX, VGA_CRTC_DATA, index as u8, value);
+    }
+
+    let gc_start = crtc_start + NUM_CRTC_REGS;
+    let gc = &registers[gc_start..gc_start + NUM_GC_REGS];
+    for (index, &value) in gc.iter().enumerate() {
+        write_indexed(VGA_GC_INDEX, VGA_GC_DATA, index as u8, value);
+    }
+
+    // the attribute controller flips between index and data on every write,
+    // reading the input status register resets it to index
+    let mut instat = Port::<u8>::new(VGA_INSTAT_READ);
+    let mut ac_index = Port::<u8>::new(VGA_AC_INDEX);
+    let mut ac_write = Port::<u8>::new(VGA_AC_WRITE);
+    let ac_start = gc_start + NUM_GC_REGS;
+    let ac = &registers[ac_start..ac_start + NUM_AC_REGS];
+    for (index, &value) in ac.iter().enumerate() {
+        instat.read();
+        ac_index.write(index as u8);
+        ac_write.write(value);
+    }
+
+    // lock the palette and unblank the display
+    instat.read();
+    ac_index.write(0x20);
+}
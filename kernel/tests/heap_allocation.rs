@@ -7,65 +7,109 @@
 extern crate alloc;
 
 use alloc::{boxed::Box, vec::Vec};
-use kernel::allocator::HEAP_SIZE;
+use kernel::allocator::{self, HEAP_SIZE};
 use bootloader::{entry_point, BootInfo};
 use core::panic::PanicInfo;
-use kernel::test_registry;
+
+// Runs against whichever global allocator the alloc-* feature picked, see the
+// test-alloc-* aliases in .cargo/config.toml. The tests in main.rs cover the rest.
 
 entry_point!(main);
 
 fn main(boot_info: &'static BootInfo) -> ! {
-    use kernel::allocator;
-    use kernel::memory::{self, BootInfoFrameAllocator};
+    use kernel::memory::{self, BitmapFrameAllocator};
     use x86_64::VirtAddr;
-    
+
     kernel::init();
     let phys_mem_offset = VirtAddr::new(boot_info.physical_memory_offset);
     let mut mapper = unsafe { memory::init(phys_mem_offset) };
-    let mut frame_allocator = unsafe { BootInfoFrameAllocator::init(&boot_info.memory_map) };
-    allocator::init_heap(&mut mapper, &mut frame_allocator).expect("heap initialization failed");    
+    let mut frame_allocator = unsafe { BitmapFrameAllocator::init(&boot_info.memory_map) };
+    allocator::init_heap(&mut mapper, &mut frame_allocator).expect("heap initialization failed");
+    // so the heap can grow
+    *memory::MAPPER.lock() = Some(mapper);
+    *memory::FRAME_ALLOCATOR.lock() = Some(frame_allocator);
 
     test_main();
     loop {}
 }
 
-// ALL tests are now in main.rs
-// #[test_case]
-// fn simple_allocation() {
-//     let heap_value_1 = Box::new(41);
-//     let heap_value_2 = Box::new(13);
-//     assert_eq!(*heap_value_1, 41);
-//     assert_eq!(*heap_value_2, 13);
-// }
-
-// pub fn large_vec() {
-//     let n = 1000;
-//     let mut vec = Vec::new();
-//     for i in 0..n {
-//         vec.push(i);
-//     }
-//     assert_eq!(vec.iter().sum::<u64>(), (n - 1) * n / 2);
-// }
-
-// #[test_case]
-// fn many_boxes() {
-//     for i in 0..HEAP_SIZE {
-//         let x = Box::new(i);
-//         assert_eq!(*x, i);
-//     }
-// }
-
-
-// fn many_boxes_long_lived() {
-//     let long_lived = Box::new(1); // new
-//     for i in 0..HEAP_SIZE {
-//         let x = Box::new(i);
-//         assert_eq!(*x, i);
-//     }
-//     assert_eq!(*long_lived, 1); // new
-// }
+#[test_case]
+fn simple_allocation() {
+    let heap_value_1 = Box::new(41);
+    let heap_value_2 = Box::new(13);
+    assert_eq!(*heap_value_1, 41);
+    assert_eq!(*heap_value_2, 13);
+}
+
+#[test_case]
+fn large_vec() {
+    let n = 1000;
+    let mut vec = Vec::new();
+    for i in 0..n {
+        vec.push(i);
+    }
+    assert_eq!(vec.iter().sum::<u64>(), (n - 1) * n / 2);
+}
+
+#[test_case]
+fn many_boxes() {
+    for i in 0..HEAP_SIZE {
+        let x = Box::new(i);
+        assert_eq!(*x, i);
+    }
+}
+
+// the bump allocator only gets through this because the heap grows
+#[test_case]
+fn many_boxes_long_lived() {
+    let long_lived = Box::new(1); // new
+    for i in 0..HEAP_SIZE {
+        let x = Box::new(i);
+        assert_eq!(*x, i);
+    }
+    assert_eq!(*long_lived, 1); // new
+}
+
+#[test_case]
+fn heap_growth() {
+    // more live data than the heap starts out with
+    let blocks: Vec<Box<[u8; 1024]>> = (0..HEAP_SIZE / 1024 + 50).map(|_| Box::new([7; 1024])).collect();
+    let grown = allocator::heap_size();
+    assert!(grown > HEAP_SIZE);
+    assert!(grown <= allocator::heap_limit());
+    assert!(blocks.iter().all(|block| block[1023] == 7));
+
+    // and shrinks again once the memory at its top is free (the bump allocator can't free it)
+    drop(blocks);
+    #[cfg(not(feature = "alloc-bump"))]
+    assert!(allocator::heap_size() < grown);
+}
+
+#[test_case]
+fn heap_counters() {
+    let before = allocator::heap_stats();
+    let small = Box::new(7u64);
+    let large = Vec::<u8>::with_capacity(4096); // bigger than the largest block
+    let during = allocator::heap_stats();
+    assert_eq!(during.live_allocations, before.live_allocations + 2);
+    // the debug allocator asks the heap for more, for its header and red zones
+    #[cfg(not(feature = "debug-alloc"))]
+    assert_eq!(during.bytes_allocated, before.bytes_allocated + 8 + 4096);
+    #[cfg(all(feature = "alloc-fixed-block", not(feature = "debug-alloc")))]
+    assert_eq!(during.fallback_allocations, before.fallback_allocations + 1);
+    assert!(during.peak_bytes >= during.bytes_allocated);
+
+    drop(small);
+    drop(large);
+    let after = allocator::heap_stats();
+    assert_eq!(after.live_allocations, before.live_allocations);
+    assert_eq!(after.bytes_allocated, before.bytes_allocated);
+    if let Some(classes) = allocator::size_classes() {
+        assert!(classes[0].free >= 1); // the Box's block is parked for reuse
+    }
+}
 
 #[panic_handler]
 fn panic(info: &PanicInfo) -> ! {
     kernel::test_panic_handler(info)
-}
\ No newline at end of file
+}
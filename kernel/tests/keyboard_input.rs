@@ -0,0 +1,99 @@
+#![no_std]
+#![no_main]
+#![feature(custom_test_frameworks)]
+#![test_runner(kernel::test_runner)]
+#![reexport_test_harness_main = "test_main"]
+
+extern crate alloc;
+
+use alloc::string::String;
+use bootloader::{entry_point, BootInfo};
+use core::panic::PanicInfo;
+use kernel::task::{executor::Executor, keyboard, Task};
+use kernel::{command_registry, console, vga_buffer};
+use spin::Mutex;
+
+entry_point!(main);
+
+// The executor running the keyboard task. The scancode stream can only be created once,
+// so all tests share it. There is one core and only the tests touch it.
+struct SharedExecutor(Mutex<Option<Executor>>);
+
+unsafe impl Sync for SharedExecutor {}
+
+static EXECUTOR: SharedExecutor = SharedExecutor(Mutex::new(None));
+
+fn main(boot_info: &'static BootInfo) -> ! {
+    use kernel::allocator;
+    use kernel::memory::{self, BitmapFrameAllocator};
+    use x86_64::VirtAddr;
+
+    kernel::init();
+    let phys_mem_offset = VirtAddr::new(boot_info.physical_memory_offset);
+    let mut mapper = unsafe { memory::init(phys_mem_offset) };
+    let mut frame_allocator = unsafe { BitmapFrameAllocator::init(&boot_info.memory_map) };
+    allocator::init_heap(&mut mapper, &mut frame_allocator).expect("heap initialization failed");
+    // the heap grows and the executor's task cache takes its slabs from these
+    *memory::MAPPER.lock() = Some(mapper);
+    *memory::FRAME_ALLOCATOR.lock() = Some(frame_allocator);
+    command_registry::register_linked_commands();
+
+    let mut executor = Executor::new();
+    executor.spawn(Task::new(keyboard::print_keypress()));
+    executor.run_until_idle(); // creates the scancode queue
+    *EXECUTOR.0.lock() = Some(executor);
+
+    test_main();
+    loop {}
+}
+
+// types `text` on the keyboard and returns everything printed while the keyboard task handled it
+fn type_text(text: &str) -> String {
+    vga_buffer::start_capture();
+    keyboard::inject_text(text);
+    EXECUTOR.0.lock().as_mut().expect("executor not set up").run_until_idle();
+    vga_buffer::take_capture()
+}
+
+// the bottom three rows of the active console, top to bottom
+fn bottom_rows() -> [String; 3] {
+    let writer = console::active_writer().lock();
+    let height = writer.height();
+    [writer.row_text(height - 3), writer.row_text(height - 2), writer.row_text(height - 1)]
+}
+
+#[test_case]
+fn echo_prints_its_arguments() {
+    let output = type_text("echo hi\n");
+    assert_eq!(output, "echo hi\nhi\n> ");
+
+    // the test runner printed the test name on the line the command was typed on
+    let [command, result, prompt] = bottom_rows();
+    assert!(command.ends_with("echo hi"));
+    assert_eq!(result, "hi");
+    assert_eq!(prompt, ">");
+}
+
+#[test_case]
+fn shift_types_uppercase_and_symbols() {
+    let output = type_text("echo Hello, World!\n");
+    assert!(output.ends_with("\nHello, World!\n> "));
+}
+
+#[test_case]
+fn backspace_edits_the_line() {
+    let output = type_text("echo hj\x08i\n");
+    assert!(output.ends_with("\nhi\n> "));
+    assert_eq!(bottom_rows()[1], "hi");
+}
+
+#[test_case]
+fn unknown_command_reports_an_error() {
+    let output = type_text("nope\n");
+    assert!(output.contains("nope not found"));
+}
+
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    kernel::test_panic_handler(info)
+}
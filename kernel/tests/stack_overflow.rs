@@ -2,7 +2,7 @@
 #![no_main]
 #![feature(abi_x86_interrupt)]
 
-use kernel::{print, println};
+use kernel::{print, println, qemu::{exit, QemuExitCode}};
 use core::panic::PanicInfo;
 use lazy_static::lazy_static;
 use x86_64::structures::idt::{InterruptDescriptorTable, InterruptStackFrame};
@@ -48,7 +48,7 @@ extern "x86-interrupt" fn test_double_fault_handler(
     _error_code: u64,
 ) -> ! {
     println!("[ok]");
-    loop {}
+    exit(QemuExitCode::Success);
 }
 
 #[panic_handler]